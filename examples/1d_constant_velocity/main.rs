@@ -1,6 +1,13 @@
-use kalman_filtering_rs::{make_k, make_m, new_cov, write_to_file};
-use peroxide::prelude::{eye, matrix, Matrix, Shape::Row};
-use plotly::{common::Title, layout::Axis, Layout, Plot, Scatter};
+use kalman_filtering_rs::{
+    consistency::ConsistencyHistory,
+    make_k, make_m, make_s,
+    models::{ConstantVelocity, MotionModel},
+    new_cov,
+    plotting::{estimate_figure, residual_figure, PlotOutput, Quantity, Series},
+    rng::default_rng,
+    write_to_file,
+};
+use peroxide::prelude::{eye, matrix, Shape::Row};
 use rand_distr::{Distribution, Normal};
 
 const SPEED: f64 = 2.0;
@@ -9,6 +16,7 @@ const DURATION: f64 = 60.0;
 const SIGMA: f64 = 5.0;
 const Q: f64 = 0.01;
 const WRITE: bool = false;
+const CONFIDENCE: f64 = 0.95;
 
 fn main() {
     let data = get_data();
@@ -16,16 +24,21 @@ fn main() {
     let mut p = eye(2);
 
     let h = matrix(vec![1.0, 0.0], 1, 2, Row);
-    let phi = phi();
+    let model = ConstantVelocity::new(1, Q);
+    let phi = model.phi(TS);
 
     let mut position_history = vec![];
     let mut speed_history = vec![];
 
-    let q = q();
+    let q = model.q(TS);
 
     let mut x_residual = vec![];
     let mut x_m_residual = vec![];
     let mut v_residual = vec![];
+    let mut x_sigma = vec![];
+    let mut v_sigma = vec![];
+
+    let mut consistency = ConsistencyHistory::new(1, 2);
 
     for i in 0..data.t.len() {
         let r = matrix(vec![SIGMA], 1, 1, Row);
@@ -44,6 +57,9 @@ fn main() {
 
         let x_tilde = x_star - x_bar;
 
+        let s = make_s(&m, &h, &r);
+        consistency.record_nis(&matrix(vec![x_tilde], 1, 1, Row), &s);
+
         let x_hat = x_bar + k1 * x_tilde;
         let xdot_hat = xdot_bar + k2 * x_tilde;
 
@@ -56,71 +72,75 @@ fn main() {
 
         x = matrix(vec![x_hat, xdot_hat], 2, 1, Row);
         p = new_cov(&k, &h, &m);
+        x_sigma.push(p[(0, 0)].sqrt());
+        v_sigma.push(p[(1, 1)].sqrt());
+
+        let truth = matrix(vec![data.x[i], SPEED], 2, 1, Row);
+        consistency.record_nees(&truth, &x, &p);
     }
 
-    // Distance
-    let mut plot = Plot::new();
-    let measurement_trace = Scatter::new(data.t.clone(), data.x_m.clone()).name("Measurement");
-    let true_trace = Scatter::new(data.t.clone(), data.x.clone()).name("True");
-    let filter_trace = Scatter::new(data.t.clone(), position_history).name("Filter");
-    plot.add_traces(vec![measurement_trace, true_trace, filter_trace]);
-    let layout = Layout::default()
-        .title(Title::new("Position"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("Position (m)")));
-    plot.set_layout(layout);
-    let position_plot = plot.to_inline_html("position-plot");
-    plot.show();
-
-    let mut plot = Plot::new();
-
-    // Speed
-    let measurement_trace = Scatter::new(
-        vec![data.t[0], data.t[data.t.len() - 1]],
-        vec![SPEED, SPEED],
-    )
-    .name("True");
-    let filter_trace = Scatter::new(data.t.clone(), speed_history).name("Filter");
-    plot.add_traces(vec![measurement_trace, filter_trace]);
-    let layout = Layout::default()
-        .title(Title::new("Velocity"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("Velocity (m/s)")));
-    plot.set_layout(layout);
-    let velocity_plot = plot.to_inline_html("velocity-plot");
-    plot.show();
-
-    // Position Residual
-
-    let mut plot = Plot::new();
-    let trace = Scatter::new(data.t.clone(), x_residual).name("Filter to true residual");
-    let trace2 = Scatter::new(data.t.clone(), x_m_residual).name("Measurement to true residual");
-    plot.add_traces(vec![trace2, trace]);
-    let layout = Layout::default()
-        .title(Title::new("Position Residual"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("Position (m)")));
-    plot.set_layout(layout);
-    let position_residual = plot.to_inline_html("position-residual");
-    plot.show();
-
-    // Velocity residual
-    let mut plot = Plot::new();
-    let trace = Scatter::new(data.t.clone(), v_residual);
-    plot.add_traces(vec![trace]);
-    let layout = Layout::default()
-        .title(Title::new("Velocity Residual"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("Velocity (m/s)")));
-    plot.set_layout(layout);
-    let velocity_residual = plot.to_inline_html("velocity-residual");
-    plot.show();
+    // Every step has a measurement, so the histories are not empty
+    let (nis_lower, nis_upper) = consistency.average_nis_bounds(CONFIDENCE).unwrap();
+    let (nees_lower, nees_upper) = consistency.average_nees_bounds(CONFIDENCE).unwrap();
+    println!(
+        "Average NIS {:.3} ({:.3} - {:.3}), consistent: {}",
+        consistency.average_nis().unwrap(),
+        nis_lower,
+        nis_upper,
+        consistency.nis_consistent(CONFIDENCE).unwrap()
+    );
+    println!(
+        "Average NEES {:.3} ({:.3} - {:.3}), consistent: {}",
+        consistency.average_nees().unwrap(),
+        nees_lower,
+        nees_upper,
+        consistency.nees_consistent(CONFIDENCE).unwrap()
+    );
+
+    let position = Quantity::new("Position", "m");
+    let velocity = Quantity::new("Velocity", "m/s");
+    let speed = vec![SPEED; data.t.len()];
+
+    let position_plot = estimate_figure(
+        &data.t,
+        &position_history,
+        &position,
+        Some(data.x.as_slice()),
+        Some(data.x_m.as_slice()),
+    );
+    let velocity_plot = estimate_figure(
+        &data.t,
+        &speed_history,
+        &velocity,
+        Some(speed.as_slice()),
+        None,
+    );
+    let mut position_residual = residual_figure(&data.t, &x_residual, &x_sigma, &position);
+    position_residual.add(Series::new(
+        "Measurement residual",
+        data.t.clone(),
+        x_m_residual,
+    ));
+    let velocity_residual = residual_figure(&data.t, &v_residual, &v_sigma, &velocity);
+
+    let output = PlotOutput::from_env();
+    let figures = [
+        ("position-plot", &position_plot),
+        ("velocity-plot", &velocity_plot),
+        ("position-residual", &position_residual),
+        ("velocity-residual", &velocity_residual),
+    ];
+    for (name, figure) in figures {
+        figure.export(name, &output).unwrap();
+    }
 
     if WRITE {
-        write_to_file("position-plot.html.tera", &position_plot);
-        write_to_file("velocity-plot.html.tera", &velocity_plot);
-        write_to_file("position-residual.html.tera", &position_residual);
-        write_to_file("velocity-residual.html.tera", &velocity_residual);
+        for (name, figure) in figures {
+            write_to_file(
+                &format!("{}.html.tera", name),
+                &figure.to_plotly().to_inline_html(name),
+            );
+        }
     }
 }
 
@@ -131,7 +151,7 @@ fn get_data() -> Data {
     let mut x = 0.0;
     let mut t = 0.0;
 
-    let mut rng = rand::thread_rng();
+    let mut rng = default_rng();
     let normal = Normal::new(0.0, SIGMA).unwrap();
 
     while t < DURATION {
@@ -153,26 +173,6 @@ fn get_data() -> Data {
     };
 }
 
-fn q() -> Matrix {
-    let q = matrix(
-        vec![
-            TS.powf(3.0) / 3.0,
-            TS.powf(2.0) / 2.0,
-            TS.powf(2.0) / 2.0,
-            TS,
-        ],
-        2,
-        2,
-        Row,
-    );
-
-    return Q * q;
-}
-
-fn phi() -> Matrix {
-    return matrix(vec![1.0, TS, 0.0, 1.0], 2, 2, Row);
-}
-
 struct Data {
     pub t: Vec<f64>,
     pub x: Vec<f64>,
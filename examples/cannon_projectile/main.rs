@@ -1,6 +1,13 @@
-use kalman_filtering_rs::{make_k, make_m, new_cov, write_to_file};
+use kalman_filtering_rs::{
+    autodiff::{hessians, HyperDual},
+    ekf::{iterated_ekf_update, second_order_ekf_update},
+    gating::{Gate, GatePolicy},
+    make_m,
+    plotting::{ellipse_series, residual_figure, Figure, PlotOutput, Quantity, Series},
+    rng::default_rng,
+    write_to_file, Convergence,
+};
 use peroxide::prelude::{matrix, zeros, Matrix, Shape::Row};
-use plotly::{common::Title, layout::Axis, Layout, Plot, Scatter};
 use rand_distr::{Distribution, Normal};
 
 const G: f64 = 9.81;
@@ -11,6 +18,12 @@ const THETA_ERROR: f64 = 0.01; // radians
 const R_ERROR: f64 = 20.5; // m
 const RADAR_DIST: f64 = 30_500.0; // m
 const EKFQ: f64 = 0.1;
+const GATE_PROBABILITY: f64 = 0.999;
+const IEKF_ITERATIONS: usize = 10;
+const IEKF_TOLERANCE: f64 = 1e-3; // m
+const SECOND_ORDER: bool = false; // Second-order EKF in place of the IEKF
+const CONFIDENCE: f64 = 0.95;
+const ELLIPSE_EVERY: usize = 200; // Steps between position uncertainty ellipses
 const WRITE: bool = false;
 
 fn main() {
@@ -66,6 +79,19 @@ fn efk_sim() {
     let mut r_residual = vec![];
     let mut theta_residual = vec![];
 
+    let mut x_sigma = vec![];
+    let mut y_sigma = vec![];
+    let mut r_sigma = vec![];
+    let mut theta_sigma = vec![];
+
+    let mut gate = Gate::from_probability(2, GATE_PROBABILITY, GatePolicy::Reject);
+    let convergence = Convergence {
+        max_iterations: IEKF_ITERATIONS,
+        tolerance: IEKF_TOLERANCE,
+    };
+    let mut iterations = 0;
+    let mut ellipses = vec![];
+
     for i in 0..data.r_measurements.len() {
         let theta_star = data.theta_measurements[i];
         let r_star = data.r_measurements[i];
@@ -86,52 +112,58 @@ fn efk_sim() {
         let y_bar = ykminus1 + TS * ydotkminus1 - 0.5 * G * TS.powf(2.0);
         let ydot_bar = ydotkminus1 - G * TS;
 
-        let theta_bar = y_bar.atan2(x_bar - RADAR_DIST);
-        // let theta_bar = (y_bar / x_bar - RADAR_DIST).atan();
-        let r_bar = ((x_bar - RADAR_DIST).powf(2.0) + y_bar.powf(2.0)).sqrt();
-
-        let h = matrix(
-            vec![
-                -y_bar / r_bar.powf(2.0),
-                0.0,
-                (x_bar - RADAR_DIST) / r_bar.powf(2.0),
-                0.0,
-                (x_bar - RADAR_DIST) / r_bar,
-                0.0,
-                y_bar / r_bar,
-                0.0,
-            ],
-            2,
-            4,
-            Row,
-        );
+        let state_bar = matrix(vec![x_bar, xdot_bar, y_bar, ydot_bar], 4, 1, Row);
+        let z = matrix(vec![theta_star, r_star], 2, 1, Row);
 
         let m = make_m(&phi, &cov, &q);
-        let k = make_k(&m, &h, &r_noise);
-
-        let k11 = k[(0, 0)];
-        let k12 = k[(0, 1)];
-        let k21 = k[(1, 0)];
-        let k22 = k[(1, 1)];
-        let k31 = k[(2, 0)];
-        let k32 = k[(2, 1)];
-        let k41 = k[(3, 0)];
-        let k42 = k[(3, 1)];
 
-        let theta_tilde = theta_star - theta_bar;
-        let r_tilde = r_star - r_bar;
-
-        let x_hat = x_bar + k11 * theta_tilde + k12 * r_tilde;
-        let xdot_hat = xdot_bar + k21 * theta_tilde + k22 * r_tilde;
-        let y_hat = y_bar + k31 * theta_tilde + k32 * r_tilde;
-        let ydot_hat = ydot_bar + k41 * theta_tilde + k42 * r_tilde;
+        let innovation = &z - &measurement(&state_bar);
+        let h = measurement_jacobian(&state_bar);
+        match gate.check(&innovation, &m, &h, &r_noise) {
+            Some(gated) => {
+                let z_gated = &measurement(&state_bar) + &gated.innovation;
+                let update = if SECOND_ORDER {
+                    second_order_ekf_update(
+                        &state_bar,
+                        &m,
+                        &z_gated,
+                        measurement,
+                        measurement_jacobian,
+                        measurement_hessians,
+                        &gated.r,
+                    )
+                } else {
+                    // Relinearise about the posterior, the prior is poor early on
+                    iterated_ekf_update(
+                        &state_bar,
+                        &m,
+                        &z_gated,
+                        measurement,
+                        measurement_jacobian,
+                        &gated.r,
+                        &convergence,
+                    )
+                };
+                iterations += update.iterations;
+                state = update.x_hat;
+                cov = update.p;
+            }
+            None => {
+                state = state_bar;
+                cov = m;
+            }
+        }
+
+        if i % ELLIPSE_EVERY == ELLIPSE_EVERY - 1 {
+            ellipses.push(ellipse_series(&state, &cov, [0, 2], CONFIDENCE));
+        }
+
+        let x_hat = state[(0, 0)];
+        let y_hat = state[(2, 0)];
 
         x_filter.push(x_hat);
         y_filter.push(y_hat);
 
-        state = matrix(vec![x_hat, xdot_hat, y_hat, ydot_hat], 4, 1, Row);
-        cov = new_cov(&k, &h, &m);
-
         x_residual.push(x_hat - data.x[i]);
         y_residual.push(y_hat - data.y[i]);
 
@@ -146,112 +178,105 @@ fn efk_sim() {
 
         r_residual.push(r_hat - data.r[i]);
         theta_residual.push(theta_hat - data.theta[i]);
+
+        // Polar uncertainty of the estimate, through the measurement Jacobian
+        let h = measurement_jacobian(&state);
+        let polar_cov = &(&h * &cov) * &h.t();
+        x_sigma.push(cov[(0, 0)].sqrt());
+        y_sigma.push(cov[(2, 2)].sqrt());
+        theta_sigma.push(polar_cov[(0, 0)].sqrt());
+        r_sigma.push(polar_cov[(1, 1)].sqrt());
     }
 
-    // Plotting
-    let mut full_plot = Plot::new();
-    let ideal_trace = Scatter::new(data.x.clone(), data.y.clone()).name("Theory");
-    full_plot.add_trace(ideal_trace);
-
-    let measurement_trace = Scatter::new(x_measurements, y_measurements).name("Measurements");
-    full_plot.add_trace(measurement_trace);
-
-    let filter_trace = Scatter::new(x_filter, y_filter).name("Filter");
-    full_plot.add_trace(filter_trace);
-
-    let layout = Layout::default().title(Title::new("EKF"));
-    full_plot.set_layout(layout);
-    full_plot.show();
-
-    // Plotting X Residuals
-    let mut x_residual_plot = Plot::new();
-    let trace =
-        Scatter::new(data.time.clone(), x_measurement_residual).name("x measurements residuals");
-    x_residual_plot.add_trace(trace);
-
-    let trace = Scatter::new(data.time.clone(), x_residual).name("x residuals");
-    x_residual_plot.add_trace(trace);
-    let layout = Layout::default()
-        .title(Title::new("x Residual - EKF"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("x (m)")));
-
-    x_residual_plot.set_layout(layout);
-    x_residual_plot.show();
-
-    // Plotting Y residuals
-    let mut y_residual_plot = Plot::new();
-    let trace =
-        Scatter::new(data.time.clone(), y_measurement_residual).name("y measurements residuals");
-    y_residual_plot.add_trace(trace);
-
-    let trace = Scatter::new(data.time.clone(), y_residual).name("y residuals");
-    y_residual_plot.add_trace(trace);
-    let layout = Layout::default()
-        .title(Title::new("y Residual - EKF"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("y (m)")));
-
-    y_residual_plot.set_layout(layout);
-    y_residual_plot.show();
-
-    // Plotting r Residuals
-    let mut r_residual_plot = Plot::new();
-    let trace =
-        Scatter::new(data.time.clone(), r_measurement_residual).name("r measurements residuals");
-    r_residual_plot.add_trace(trace);
-
-    let trace = Scatter::new(data.time.clone(), r_residual).name("r residuals");
-    r_residual_plot.add_trace(trace);
-    let layout = Layout::default()
-        .title(Title::new("r Residual - EKF"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("r (m)")));
-
-    r_residual_plot.set_layout(layout);
-    r_residual_plot.show();
-
-    // Plotting theta residuals
-    let mut theta_residual_plot = Plot::new();
-    let trace = Scatter::new(data.time.clone(), theta_measurement_residual)
-        .name("theta measurements residuals");
-    theta_residual_plot.add_trace(trace);
-
-    let trace = Scatter::new(data.time.clone(), theta_residual).name("theta residuals");
-    theta_residual_plot.add_trace(trace);
-    let layout = Layout::default()
-        .title(Title::new("theta Residual - EKF"))
-        .x_axis(Axis::default().title(Title::new("theta (radians)")))
-        .y_axis(
-            Axis::default()
-                .title(Title::new("y (m)"))
-                .range(vec![-0.5, 0.5]),
+    println!(
+        "Gated {} of {} measurements",
+        gate.gated_steps().len(),
+        gate.history.len()
+    );
+    let updates = gate.history.len() - gate.gated_steps().len();
+    if updates > 0 {
+        println!(
+            "Average of {:.2} linearisations per update",
+            iterations as f64 / updates as f64
         );
+    }
 
-    theta_residual_plot.set_layout(layout);
-    theta_residual_plot.show();
+    // Plotting
+    let mut full_figure = Figure::new("EKF", "x (m)", "y (m)");
+    full_figure.add(Series::new("Theory", data.x.clone(), data.y.clone()));
+    full_figure.add(Series::new("Measurements", x_measurements, y_measurements));
+    full_figure.add(Series::new("Filter", x_filter, y_filter));
+    for (i, ellipse) in ellipses.into_iter().enumerate() {
+        full_figure.add(if i == 0 {
+            ellipse
+        } else {
+            ellipse.without_legend()
+        });
+    }
+    let output = PlotOutput::from_env();
+    full_figure.export("ekf", &output).unwrap();
+
+    // Residuals, with the measurement residuals for comparison
+    let residuals = [
+        (
+            "x-residual-ekf",
+            Quantity::new("x", "m"),
+            x_residual,
+            x_sigma,
+            x_measurement_residual,
+            None,
+        ),
+        (
+            "y-residual-ekf",
+            Quantity::new("y", "m"),
+            y_residual,
+            y_sigma,
+            y_measurement_residual,
+            None,
+        ),
+        (
+            "r-residual-ekf",
+            Quantity::new("r", "m"),
+            r_residual,
+            r_sigma,
+            r_measurement_residual,
+            None,
+        ),
+        (
+            "theta-residual-ekf",
+            Quantity::new("theta", "radians"),
+            theta_residual,
+            theta_sigma,
+            theta_measurement_residual,
+            Some((-0.5, 0.5)),
+        ),
+    ];
+    let mut residual_figures = vec![];
+    for (name, quantity, residual, sigma, measurement_residual, y_range) in residuals {
+        let mut figure = residual_figure(&data.time, &residual, &sigma, &quantity);
+        figure.add(Series::new(
+            "Measurement residual",
+            data.time.clone(),
+            measurement_residual,
+        ));
+        if let Some((min, max)) = y_range {
+            figure = figure.with_y_range(min, max);
+        }
+        figure.export(name, &output).unwrap();
+        residual_figures.push((name, figure));
+    }
 
     if WRITE {
         write_to_file(
             "full-plot-ekf.html.tera",
-            &full_plot.to_inline_html("full-plot-ekf"),
-        );
-        write_to_file(
-            "x-residual-ekf.html.tera",
-            &x_residual_plot.to_inline_html("x-residual-ekf"),
-        );
-        write_to_file(
-            "y-residual-ekf.html.tera",
-            &y_residual_plot.to_inline_html("y-residual-ekf"),
-        );
-        write_to_file(
-            "r-residual-ekf.html.tera",
-            &r_residual_plot.to_inline_html("r-residual-ekf"),
-        );
-        write_to_file(
-            "theta-residual-ekf.html.tera",
-            &theta_residual_plot.to_inline_html("theta-residual-ekf"),
+            &full_figure.to_plotly().to_inline_html("full-plot-ekf"),
         );
+        for (name, figure) in &residual_figures {
+            write_to_file(
+                &format!("{}.html.tera", name),
+                &figure.to_plotly().to_inline_html(*name),
+            );
+        }
     }
 }
 
@@ -274,7 +299,7 @@ fn get_data() -> Data {
 
     let theta_normal = Normal::new(0.0, THETA_ERROR).unwrap();
     let r_normal = Normal::new(0.0, R_ERROR).unwrap();
-    let mut rng = rand::thread_rng();
+    let mut rng = default_rng();
 
     while y >= 0.0 {
         t_history.push(t);
@@ -327,6 +352,46 @@ fn r(x: f64, y: f64) -> f64 {
     return ((x - RADAR_DIST).powf(2.0) + y.powf(2.0)).powf(0.5);
 }
 
+// [theta, r] seen by the radar
+fn measurement(state: &Matrix) -> Matrix {
+    let x = state[(0, 0)];
+    let y = state[(2, 0)];
+    return matrix(vec![theta(x, y), r(x, y)], 2, 1, Row);
+}
+
+fn measurement_jacobian(state: &Matrix) -> Matrix {
+    let dx = state[(0, 0)] - RADAR_DIST;
+    let y = state[(2, 0)];
+    let range = r(state[(0, 0)], y);
+    return matrix(
+        vec![
+            -y / range.powf(2.0),
+            0.0,
+            dx / range.powf(2.0),
+            0.0,
+            dx / range,
+            0.0,
+            y / range,
+            0.0,
+        ],
+        2,
+        4,
+        Row,
+    );
+}
+
+// The polar measurement over hyper-dual numbers, differentiated exactly for the
+// second-order filter
+fn polar(state: &[HyperDual]) -> Vec<HyperDual> {
+    let dx = state[0] - RADAR_DIST;
+    let y = state[2];
+    return vec![y.atan2(dx), (dx * dx + y * y).sqrt()];
+}
+
+fn measurement_hessians(state: &Matrix) -> Vec<Matrix> {
+    return hessians(&polar, state);
+}
+
 fn x(r: f64, theta: f64) -> f64 {
     return r * theta.cos() + RADAR_DIST;
 }
@@ -1,10 +1,15 @@
-use kalman_filtering_rs::{make_k, make_m, new_cov};
+use kalman_filtering_rs::{
+    make_k, make_m, new_cov,
+    plotting::{estimate_figure, residual_figure, PlotOutput, Quantity},
+    rng::default_rng,
+};
 use peroxide::prelude::{matrix, Shape::Row};
-use plotly::{Plot, Scatter};
 use rand_distr::{Distribution, Normal};
 
+const TRUTH: f64 = 0.0;
+
 fn main() {
-    let measurements = gen_measurements(0.0, 20, 10.0);
+    let measurements = gen_measurements(TRUTH, 20, 10.0);
 
     let mut x = matrix(vec![10.0], 1, 1, Row);
     let mut p = matrix(vec![5.0], 1, 1, Row);
@@ -15,6 +20,7 @@ fn main() {
     let r = matrix(vec![10.0], 1, 1, Row);
 
     let mut history = vec![];
+    let mut sigma = vec![];
     for measurement in &measurements {
         let x_star = *measurement;
 
@@ -33,26 +39,42 @@ fn main() {
         p = new_cov(&k, &h, &m);
 
         history.push(x.data[0]);
+        sigma.push(p.data[0].sqrt());
     }
 
     let mut inex = vec![];
 
     for i in 0..history.len() {
-        inex.push(i);
+        inex.push(i as f64);
     }
 
-    let mut plot = Plot::new();
-    let h_trace = Scatter::new(inex.clone(), history).name("Filter");
-    let m_trace = Scatter::new(inex, measurements).name("Measurements");
-    plot.add_trace(h_trace);
-    plot.add_trace(m_trace);
-    plot.show();
+    let quantity = Quantity::new("x", "m");
+    let truth = vec![TRUTH; history.len()];
+    let residual: Vec<f64> = history.iter().map(|x| x - TRUTH).collect();
+
+    let mut estimate_plot = estimate_figure(
+        &inex,
+        &history,
+        &quantity,
+        Some(truth.as_slice()),
+        Some(measurements.as_slice()),
+    );
+    let mut residual_plot = residual_figure(&inex, &residual, &sigma, &quantity);
+
+    let output = PlotOutput::from_env();
+    for (name, figure) in [
+        ("estimate", &mut estimate_plot),
+        ("residual", &mut residual_plot),
+    ] {
+        figure.x_label = "Measurement".to_string();
+        figure.export(name, &output).unwrap();
+    }
 }
 
 fn gen_measurements(t: f64, n: usize, s: f64) -> Vec<f64> {
     let mut out = vec![];
     let normal = Normal::new(0.0, s).unwrap();
-    let mut rng = rand::thread_rng();
+    let mut rng = default_rng();
     for _i in 0..n {
         let r: f64 = normal.sample(&mut rng);
         out.push(t + r);
@@ -0,0 +1,128 @@
+use kalman_filtering_rs::{
+    continuous_discrete::ContinuousDiscreteEkf,
+    integrators::Rk45,
+    plotting::{estimate_figure, residual_figure, PlotOutput, Quantity},
+    rng::default_rng,
+    simulation::{Dataset, Simulation},
+};
+use peroxide::prelude::{matrix, Matrix, Shape::Row};
+use rand::Rng;
+
+const G: f64 = -9.81;
+const BETA: f64 = 500.0;
+const INITX: f64 = 6705.0;
+const SIGNOISE: f64 = 30.0;
+const PHIS: f64 = 1.0;
+const MIN_DT: f64 = 0.05; // Measurements arrive at irregular intervals
+const MAX_DT: f64 = 0.5;
+const DURATION: f64 = 37.0; // Impact is at about 37.8 s
+
+fn main() {
+    let data = get_data();
+    let x = data.truth_of(0);
+    let v = data.truth_of(1);
+    let x_m = data.measurement_of(0);
+
+    let mut cov = matrix(vec![0.0; 4], 2, 2, Row);
+    cov[(0, 0)] = 99999.9;
+    cov[(1, 1)] = 99999.9;
+    let q = matrix(vec![0.0, 0.0, 0.0, PHIS], 2, 2, Row);
+
+    let mut filter = ContinuousDiscreteEkf::new(
+        matrix(vec![x_m[0], 0.0], 2, 1, Row),
+        cov,
+        data.t[0],
+        dynamics,
+        jacobian,
+        q,
+    );
+
+    let h = matrix(vec![1.0, 0.0], 1, 2, Row);
+    let r = matrix(vec![SIGNOISE.powf(2.0)], 1, 1, Row);
+
+    let mut x_history = vec![];
+    let mut v_history = vec![];
+    let mut x_residual = vec![];
+    let mut v_residual = vec![];
+    let mut x_sigma = vec![];
+    let mut v_sigma = vec![];
+    let mut evaluations = 0;
+
+    for i in 0..data.t.len() {
+        filter.predict(data.t[i]).unwrap();
+        evaluations += filter.stats.evaluations;
+
+        let z = matrix(vec![x_m[i]], 1, 1, Row);
+        filter.update(&z, |x| &h * x, |_| h.clone(), &r);
+
+        x_history.push(filter.x[(0, 0)]);
+        v_history.push(filter.x[(1, 0)]);
+        x_residual.push(filter.x[(0, 0)] - x[i]);
+        v_residual.push(filter.x[(1, 0)] - v[i]);
+        x_sigma.push(filter.p[(0, 0)].sqrt());
+        v_sigma.push(filter.p[(1, 1)].sqrt());
+    }
+
+    println!(
+        "{} measurements, {} derivative evaluations",
+        data.t.len(),
+        evaluations
+    );
+
+    let output = PlotOutput::from_env();
+    let position = Quantity::new("Position", "m");
+    let velocity = Quantity::new("Velocity", "m/s");
+
+    estimate_figure(
+        &data.t,
+        &x_history,
+        &position,
+        Some(x.as_slice()),
+        Some(x_m.as_slice()),
+    )
+    .export("position", &output)
+    .unwrap();
+    estimate_figure(&data.t, &v_history, &velocity, Some(v.as_slice()), None)
+        .export("velocity", &output)
+        .unwrap();
+    residual_figure(&data.t, &x_residual, &x_sigma, &position)
+        .export("position-residual", &output)
+        .unwrap();
+    residual_figure(&data.t, &v_residual, &v_sigma, &velocity)
+        .export("velocity-residual", &output)
+        .unwrap();
+}
+
+fn get_data() -> Dataset {
+    let mut rng = default_rng();
+    let mut times = vec![0.0];
+    while times[times.len() - 1] < DURATION {
+        times.push(times[times.len() - 1] + rng.gen_range(MIN_DT..MAX_DT));
+    }
+
+    let h = matrix(vec![1.0, 0.0], 1, 2, Row);
+    let r = matrix(vec![SIGNOISE.powf(2.0)], 1, 1, Row);
+    let simulation = Simulation::from_ode(dynamics, Rk45::new(1e-9, 1e-9), move |x| &h * x, r);
+
+    return simulation.run_with_rng(&matrix(vec![INITX, 0.0], 2, 1, Row), &times, &mut rng);
+}
+
+fn rho(position: f64) -> f64 {
+    return 0.0034 * (-position / INITX).exp();
+}
+
+fn dynamics(_t: f64, state: &Matrix) -> Matrix {
+    let x = state[(0, 0)];
+    let v = state[(1, 0)];
+    // Gravity pulls down and drag opposes the motion
+    let a = G - rho(x) * G.abs() * v * v.abs() / (2.0 * BETA);
+    return matrix(vec![v, a], 2, 1, Row);
+}
+
+fn jacobian(_t: f64, state: &Matrix) -> Matrix {
+    let x = state[(0, 0)];
+    let v = state[(1, 0)];
+    let da_dx = rho(x) * G.abs() * v * v.abs() / (2.0 * INITX * BETA);
+    let da_dv = -rho(x) * G.abs() * v.abs() / BETA;
+    return matrix(vec![0.0, 1.0, da_dx, da_dv], 2, 2, Row);
+}
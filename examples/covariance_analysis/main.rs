@@ -0,0 +1,87 @@
+use kalman_filtering_rs::{
+    covariance_analysis::CovarianceAnalysis,
+    models::{ConstantVelocity, MotionModel},
+    plotting::{Figure, PlotOutput, Series, TIME_AXIS},
+};
+use peroxide::prelude::{eye, matrix, Shape::Row};
+
+// The 1d_constant_velocity filter, analysed before any data exists. That
+// filter uses SIGMA itself as the measurement variance and starts from P = I.
+const TS: f64 = 0.1;
+const DURATION: f64 = 60.0;
+const SIGMA: f64 = 5.0;
+const Q: f64 = 0.01;
+// Noise the filter will really see, the measurements have a standard
+// deviation of SIGMA
+const TRUE_Q: f64 = 0.1;
+const TRUE_SIGMA: f64 = 5.0;
+
+fn main() {
+    let h = matrix(vec![1.0, 0.0], 1, 2, Row);
+    let r = matrix(vec![SIGMA], 1, 1, Row);
+    let p0 = eye(2);
+
+    let nominal = CovarianceAnalysis::from_model(ConstantVelocity::new(1, Q), h.clone(), r.clone())
+        .run_for(&p0, 0.0, DURATION, TS);
+
+    let true_model = ConstantVelocity::new(1, TRUE_Q);
+    let true_r = matrix(vec![TRUE_SIGMA.powf(2.0)], 1, 1, Row);
+    let mismatched = CovarianceAnalysis::from_model(ConstantVelocity::new(1, Q), h, r)
+        .with_true_noise(move |dt| true_model.q(dt), true_r)
+        .run_for(&p0, 0.0, DURATION, TS);
+
+    let last = &nominal[nominal.len() - 1];
+    println!(
+        "Steady state: position std {:.3} m, velocity std {:.3} m/s, K = [{:.4}, {:.4}]",
+        last.std(0),
+        last.std(1),
+        last.gain[(0, 0)],
+        last.gain[(1, 0)]
+    );
+    let last = &mismatched[mismatched.len() - 1];
+    println!(
+        "With Q = {} and R = {}: filter reports {:.3} m, actual {:.3} m",
+        TRUE_Q,
+        TRUE_SIGMA.powf(2.0),
+        last.std(0),
+        last.true_std(0)
+    );
+
+    let t: Vec<f64> = nominal.iter().map(|step| step.t).collect();
+    let output = PlotOutput::from_env();
+
+    for (state, name, unit) in [(0, "Position", "m"), (1, "Velocity", "m/s")] {
+        let mut figure = Figure::new(
+            &format!("{} Standard Deviation", name),
+            TIME_AXIS,
+            &format!("{} ({})", name, unit),
+        );
+        figure.add(Series::new(
+            "Predicted",
+            t.clone(),
+            nominal.iter().map(|s| s.std(state)).collect(),
+        ));
+        figure.add(Series::new(
+            "Actual with mismatched noise",
+            t.clone(),
+            mismatched.iter().map(|s| s.true_std(state)).collect(),
+        ));
+        figure
+            .export(&format!("{}-std", name.to_lowercase()), &output)
+            .unwrap();
+    }
+
+    // Gains
+    let mut figure = Figure::new("Kalman Gains", TIME_AXIS, "Gain");
+    figure.add(Series::new(
+        "K1",
+        t.clone(),
+        nominal.iter().map(|s| s.gain[(0, 0)]).collect(),
+    ));
+    figure.add(Series::new(
+        "K2",
+        t,
+        nominal.iter().map(|s| s.gain[(1, 0)]).collect(),
+    ));
+    figure.export("gains", &output).unwrap();
+}
@@ -1,6 +1,10 @@
-use kalman_filtering_rs::{make_k, make_m, new_cov, write_to_file};
+use kalman_filtering_rs::{
+    make_k, make_m, new_cov,
+    plotting::{estimate_figure, residual_figure, PlotOutput, Quantity, Series},
+    rng::default_rng,
+    write_to_file,
+};
 use peroxide::prelude::{matrix, zeros, Matrix, Shape::Row};
-use plotly::{common::Title, layout::Axis, Layout, Plot, Scatter};
 use rand_distr::{Distribution, Normal};
 
 const SIGNOISE: f64 = 300.0;
@@ -35,6 +39,10 @@ fn main() {
 
     let mut x_measurement_residual = vec![];
 
+    let mut x_sigma = vec![];
+    let mut v_sigma = vec![];
+    let mut a_sigma = vec![];
+
     for i in 0..data.t.len() {
         let x_star = data.x[i];
 
@@ -72,107 +80,67 @@ fn main() {
         v_residual.push(data.v[i] - xdot_hat);
         a_residual.push(G - xdotdot_hat);
 
+        x_sigma.push(cov[(0, 0)].sqrt());
+        v_sigma.push(cov[(1, 1)].sqrt());
+        a_sigma.push(cov[(2, 2)].sqrt());
+
         x_measurement_residual.push(data.x[i] - data.s[i]);
     }
 
-    // Position Plot
-    let mut plot = Plot::new();
-    let m_trace = Scatter::new(data.t.clone(), data.s.clone()).name("Truth");
-    let x_trace = Scatter::new(data.t.clone(), x_history).name("Filter");
-    let s_trace = Scatter::new(data.t.clone(), data.x.clone()).name("Measurements");
-    let layout = Layout::default()
-        .title(Title::new("Position"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("Position (m)")));
-    plot.set_layout(layout);
-    plot.add_traces(vec![m_trace, x_trace, s_trace]);
-    let position_plot = plot.to_inline_html("position-plot");
-    plot.show();
-
-    // Velocity plot
-    let mut plot = Plot::new();
-    let filter_trace = Scatter::new(data.t.clone(), v_history).name("Filter Velocity");
-    let real_trace = Scatter::new(data.t.clone(), data.v.clone()).name("Real Velocity");
-    let layout = Layout::default()
-        .title(Title::new("Velocity"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("Velocity (m/s)")));
-    plot.set_layout(layout);
-    plot.add_traces(vec![filter_trace, real_trace]);
-    let velocity_plot = plot.to_inline_html("velocity-plot");
-    plot.show();
-
-    // Acceleration plot
-    let mut plot = Plot::new();
-    let trace = Scatter::new(data.t.clone(), a_history).name("Filter Acceleration");
-    let ideal_trace =
-        Scatter::new(vec![data.t[0], data.t[data.t.len() - 1]], vec![G, G]).name("Ideal");
-    plot.add_traces(vec![trace, ideal_trace]);
-    let layout = Layout::default()
-        .title(Title::new("Acceleration"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(
-            Axis::default()
-                .title(Title::new("Acceleration (m/s^2)"))
-                .range(vec![-20.0, 20.0]),
-        );
-    plot.set_layout(layout);
-    let acceleration_plot = plot.to_inline_html("acceleration-plot");
-    plot.show();
-
-    // Position residual
-    let mut plot = Plot::new();
-    let trace = Scatter::new(data.t.clone(), x_residual).name("Filter to true residual");
-    let trace2 =
-        Scatter::new(data.t.clone(), x_measurement_residual).name("Measurement to true residual");
-    plot.add_traces(vec![trace, trace2]);
-    let layout = Layout::default()
-        .title(Title::new("Position Residual"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("Position (m)")));
-    plot.set_layout(layout);
-    let position_residual = plot.to_inline_html("position-residual");
-    plot.show();
-
-    // Velocity residual
-    let mut plot = Plot::new();
-    let trace = Scatter::new(data.t.clone(), v_residual);
-    plot.add_traces(vec![trace]);
-    let layout = Layout::default()
-        .title(Title::new("Velocity Residual"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(
-            Axis::default()
-                .title(Title::new("Velocity (m/s)"))
-                .range(vec![-50.0, 50.0]),
-        );
-    plot.set_layout(layout);
-    let velocity_residual = plot.to_inline_html("velocity-residual");
-    plot.show();
-
-    // Acceleration residual
-    let mut plot = Plot::new();
-    let trace = Scatter::new(data.t.clone(), a_residual);
-    plot.add_traces(vec![trace]);
-    let layout = Layout::default()
-        .title(Title::new("Acceleration Residual"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(
-            Axis::default()
-                .title(Title::new("Acceleration (m/s^2)"))
-                .range(vec![-50.0, 50.0]),
-        );
-    plot.set_layout(layout);
-    let acceleration_residual = plot.to_inline_html("acceleration-residual");
-    plot.show();
+    let position = Quantity::new("Position", "m");
+    let velocity = Quantity::new("Velocity", "m/s");
+    let acceleration = Quantity::new("Acceleration", "m/s^2");
+    let g = vec![G; data.t.len()];
+
+    let position_plot = estimate_figure(
+        &data.t,
+        &x_history,
+        &position,
+        Some(data.s.as_slice()),
+        Some(data.x.as_slice()),
+    );
+    let velocity_plot = estimate_figure(
+        &data.t,
+        &v_history,
+        &velocity,
+        Some(data.v.as_slice()),
+        None,
+    );
+    let acceleration_plot =
+        estimate_figure(&data.t, &a_history, &acceleration, Some(g.as_slice()), None)
+            .with_y_range(-20.0, 20.0);
+
+    let mut position_residual = residual_figure(&data.t, &x_residual, &x_sigma, &position);
+    position_residual.add(Series::new(
+        "Measurement residual",
+        data.t.clone(),
+        x_measurement_residual,
+    ));
+    let velocity_residual =
+        residual_figure(&data.t, &v_residual, &v_sigma, &velocity).with_y_range(-50.0, 50.0);
+    let acceleration_residual =
+        residual_figure(&data.t, &a_residual, &a_sigma, &acceleration).with_y_range(-50.0, 50.0);
+
+    let output = PlotOutput::from_env();
+    let figures = [
+        ("position-plot", &position_plot),
+        ("velocity-plot", &velocity_plot),
+        ("acceleration-plot", &acceleration_plot),
+        ("position-residual", &position_residual),
+        ("velocity-residual", &velocity_residual),
+        ("acceleration-residual", &acceleration_residual),
+    ];
+    for (name, figure) in figures {
+        figure.export(name, &output).unwrap();
+    }
 
     if WRITE {
-        write_to_file("position-plot.html.tera", &position_plot);
-        write_to_file("velocity-plot.html.tera", &velocity_plot);
-        write_to_file("acceleration-plot.html.tera", &acceleration_plot);
-        write_to_file("position-residual.html.tera", &position_residual);
-        write_to_file("velocity-residual.html.tera", &velocity_residual);
-        write_to_file("acceleration-residual.html.tera", &acceleration_residual);
+        for (name, figure) in figures {
+            write_to_file(
+                &format!("{}.html.tera", name),
+                &figure.to_plotly().to_inline_html(name),
+            );
+        }
     }
 }
 
@@ -197,7 +165,7 @@ fn get_data() -> Data {
     let mut v_history = vec![];
 
     let normal = Normal::new(0.0, SIGNOISE).unwrap();
-    let mut rng = rand::thread_rng();
+    let mut rng = default_rng();
 
     while t < MAXT {
         // Measurement
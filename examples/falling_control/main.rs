@@ -1,6 +1,11 @@
-use kalman_filtering_rs::{make_k, make_m, new_cov, write_to_file};
+use kalman_filtering_rs::{
+    filter::KalmanFilter,
+    plotting::{estimate_plot, residual_plot, times, PlotOutput, Quantity, Series},
+    report::Report,
+    rng::default_rng,
+    write_to_file,
+};
 use peroxide::prelude::{matrix, zeros, Matrix, Shape::Row};
-use plotly::{common::Title, layout::Axis, Layout, Plot, Scatter};
 use rand_distr::{Distribution, Normal};
 
 const SIGNOISE: f64 = 304.8;
@@ -11,7 +16,6 @@ const WRITE: bool = true;
 fn main() {
     let measurements = get_data();
 
-    let mut state = zeros(2, 1);
     let mut cov = zeros(2, 2);
     cov[(0, 0)] = 999999999.;
     cov[(1, 1)] = 999999999.;
@@ -19,124 +23,88 @@ fn main() {
     let h = matrix(vec![1.0, 0.0], 1, 2, Row);
     let r = matrix(vec![SIGNOISE], 1, 1, Row);
 
+    let mut filter = KalmanFilter::new(zeros(2, 1), cov.clone(), 0.0, phi, q, h, r.clone())
+        .with_propagation(|x, dt| &(&phi(dt) * x) + &gravity(dt));
+
+    let mut history = vec![];
     let mut x_measurements = vec![];
     let mut x_truth = vec![];
     let mut v_truth = vec![];
-    let mut x_history = vec![];
-    let mut v_history = vec![];
-    let mut t_history = vec![];
-    let mut x_residual = vec![];
     let mut x_measurement_residual = vec![];
-    let mut v_residual = vec![];
 
-    let mut t = 0.0;
     for mea in &measurements {
-        let dt = mea.t - t;
-
-        let phi = phi(dt);
-        let q = q(dt);
-
         let x_star = mea.x;
+        let z = matrix(vec![x_star], 1, 1, Row);
+        let record = filter.step(mea.t, Some(&z));
 
-        let x_k_minus1 = state.data[0];
-        let x_dot_k_minus1 = state.data[1];
-
-        let x_tilde = x_star - x_k_minus1 - x_dot_k_minus1 * dt + 0.5 * G * dt.powf(2.0);
-
-        let m = make_m(&phi, &cov, &q);
-        let k = make_k(&m, &h, &r);
-
-        let k1 = k[(0, 0)];
-        let k2 = k[(1, 0)];
-
-        let x_hat = x_k_minus1 + x_dot_k_minus1 * dt - 0.5 * G * dt.powf(2.0) + k1 * x_tilde;
-        let x_dot_hat = x_dot_k_minus1 - G * dt + k2 * x_tilde;
-
-        state = matrix(vec![x_hat, x_dot_hat], 1, 2, Row);
-        cov = new_cov(&k, &h, &m);
-        t = mea.t;
-
-        x_history.push(state.data[0]);
-        v_history.push(state.data[1]);
+        history.push(record);
         x_measurements.push(mea.x);
         x_truth.push(mea.s);
         v_truth.push(mea.v);
-        t_history.push(t);
-        x_residual.push(x_hat - mea.s);
         x_measurement_residual.push(x_star - mea.s);
-        v_residual.push(x_dot_hat - mea.v);
     }
 
-    // Distance
-    let mut x_plot = Plot::new();
-    let m_trace = Scatter::new(t_history.clone(), x_measurements).name("Measurements");
-    let x_trace = Scatter::new(t_history.clone(), x_history).name("Filter");
-    let s_trace = Scatter::new(t_history.clone(), x_truth).name("Truth");
-    x_plot.add_traces(vec![m_trace, x_trace, s_trace]);
-    let layout = Layout::default()
-        .title(Title::new("Position"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("Position (m)")));
-    x_plot.set_layout(layout);
-    x_plot.show();
-
-    // Velocity
-    let mut v_plot = Plot::new();
-    let v_trace = Scatter::new(t_history.clone(), v_history).name("Filter Velocity");
-    let v_truth = Scatter::new(t_history.clone(), v_truth).name("True Velocity");
-    v_plot.add_traces(vec![v_trace, v_truth]);
-    let layout = Layout::default()
-        .title(Title::new("Velocity"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("Velocity (m/s)")));
-    v_plot.set_layout(layout);
-    v_plot.show();
-
-    // Distance residual
-    let mut xr_plot = Plot::new();
-    let trace = Scatter::new(t_history.clone(), x_residual).name("Distance Residual");
-    let m_trace =
-        Scatter::new(t_history.clone(), x_measurement_residual).name("Measurement Residual");
-    xr_plot.add_traces(vec![trace, m_trace]);
-    let layout = Layout::default()
-        .title(Title::new("Position Residual"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(Axis::default().title(Title::new("Position (m)")));
-    xr_plot.set_layout(layout);
-    xr_plot.show();
-
-    // velocity residual
-    let mut vr_plot = Plot::new();
-    let trace = Scatter::new(t_history.clone(), v_residual).name("Velocity Residual");
-    vr_plot.add_trace(trace);
-    let layout = Layout::default()
-        .title(Title::new("Velocity Residual"))
-        .x_axis(Axis::default().title(Title::new("t (s)")))
-        .y_axis(
-            Axis::default()
-                .title(Title::new("Velocity (m/s)"))
-                .range(vec![-50.0, 50.0]),
-        );
-    vr_plot.set_layout(layout);
-    vr_plot.show();
+    let position = Quantity::new("Position", "m");
+    let velocity = Quantity::new("Velocity", "m/s");
+
+    let x_plot = estimate_plot(
+        &history,
+        0,
+        &position,
+        Some(x_truth.as_slice()),
+        Some(x_measurements.as_slice()),
+    );
+    let v_plot = estimate_plot(&history, 1, &velocity, Some(v_truth.as_slice()), None);
+    let mut xr_plot = residual_plot(&history, 0, &position, &x_truth);
+    xr_plot.add(Series::new(
+        "Measurement Residual",
+        times(&history),
+        x_measurement_residual,
+    ));
+    let vr_plot = residual_plot(&history, 1, &velocity, &v_truth).with_y_range(-50.0, 50.0);
+
+    let output = PlotOutput::from_env();
+    for (name, figure) in [
+        ("position", &x_plot),
+        ("velocity", &v_plot),
+        ("position-residual", &xr_plot),
+        ("velocity-residual", &vr_plot),
+    ] {
+        figure.export(name, &output).unwrap();
+    }
 
     if WRITE {
         write_to_file(
             "position-plot.html.tera",
-            &x_plot.to_inline_html("position-plot"),
+            &x_plot.to_plotly().to_inline_html("position-plot"),
         );
         write_to_file(
             "velocity-plot.html.tera",
-            &v_plot.to_inline_html("velocity-plot"),
+            &v_plot.to_plotly().to_inline_html("velocity-plot"),
         );
         write_to_file(
             "position-residual.html.tera",
-            &xr_plot.to_inline_html("position-residual"),
+            &xr_plot.to_plotly().to_inline_html("position-residual"),
         );
         write_to_file(
             "velocity-residual.html.tera",
-            &vr_plot.to_inline_html("velocity-residual"),
+            &vr_plot.to_plotly().to_inline_html("velocity-residual"),
         );
+
+        let mut report = Report::new("Falling Object with Known Gravity");
+        report.add_parameter("Model", "Constant velocity with gravity as a control input");
+        report.add_matrix("Initial P", &cov);
+        report.add_matrix("Q (dt = 0.1 s)", &q(0.1));
+        report.add_matrix("R", &r);
+        let truth: Vec<Matrix> = x_truth
+            .iter()
+            .zip(v_truth.iter())
+            .map(|(x, v)| matrix(vec![*x, *v], 2, 1, Row))
+            .collect();
+        report.add_rmse(&history, &[position, velocity], &truth);
+        report.add_nis(&history, 0.95);
+        report.add_figures(vec![x_plot, v_plot, xr_plot, vr_plot]);
+        report.write("falling-control-report.html").unwrap();
     }
 }
 
@@ -156,7 +124,7 @@ fn get_data() -> Vec<Measurement> {
     let mut m = vec![];
 
     let normal = Normal::new(0.0, SIGNOISE).unwrap();
-    let mut rng = rand::thread_rng();
+    let mut rng = default_rng();
 
     while s > 0.0 {
         // Measurement
@@ -186,6 +154,10 @@ fn phi(dt: f64) -> Matrix {
     return phi;
 }
 
+fn gravity(dt: f64) -> Matrix {
+    return matrix(vec![-0.5 * G * dt.powf(2.0), -G * dt], 2, 1, Row);
+}
+
 fn q(dt: f64) -> Matrix {
     let mut q = zeros(2, 2);
 
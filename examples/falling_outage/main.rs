@@ -0,0 +1,127 @@
+use kalman_filtering_rs::{
+    filter::KalmanFilter,
+    plotting::{innovation_plot, state_plots, PlotOutput, Quantity},
+    rng::default_rng,
+};
+use peroxide::prelude::{matrix, zeros, Matrix, Shape::Row};
+use rand_distr::{Distribution, Normal};
+
+const SIGNOISE: f64 = 304.8;
+const PHIS: f64 = 1.0; // In the book this value is given as 0, but this makes little sense to me
+const G: f64 = 9.81;
+// Measurements are lost for this window and the filter coasts through it
+const OUTAGE_START: f64 = 20.0;
+const OUTAGE_END: f64 = 25.0;
+
+fn main() {
+    let measurements = get_data();
+
+    let mut cov = zeros(2, 2);
+    cov[(0, 0)] = 999999999.;
+    cov[(1, 1)] = 999999999.;
+
+    let h = matrix(vec![1.0, 0.0], 1, 2, Row);
+    let r = matrix(vec![SIGNOISE], 1, 1, Row);
+
+    let mut filter = KalmanFilter::new(zeros(2, 1), cov, 0.0, phi, q, h, r)
+        .with_propagation(|x, dt| &(&phi(dt) * x) + &gravity(dt));
+
+    let mut history = vec![];
+    let mut truth = vec![];
+
+    for mea in &measurements {
+        let z = matrix(vec![mea.x], 1, 1, Row);
+
+        // No measurements arrive during the outage, so the filter only predicts
+        let in_outage = mea.t >= OUTAGE_START && mea.t < OUTAGE_END;
+        let record = if in_outage {
+            filter.step(mea.t, None)
+        } else {
+            filter.step(mea.t, Some(&z))
+        };
+
+        history.push(record);
+        truth.push(matrix(vec![mea.s, mea.v], 2, 1, Row));
+    }
+
+    let position = Quantity::new("Position", "m");
+    let velocity = Quantity::new("Velocity", "m/s");
+
+    // The sigma envelopes widen while the filter coasts, and the innovations
+    // stop until measurements return
+    let output = PlotOutput::from_env();
+    let names = [
+        "position",
+        "position-residual",
+        "velocity",
+        "velocity-residual",
+    ];
+    let figures = state_plots(&history, &[position.clone(), velocity], &truth);
+    for (name, figure) in names.iter().zip(figures.iter()) {
+        figure.export(name, &output).unwrap();
+    }
+    innovation_plot(&history, 0, &position)
+        .export("innovation", &output)
+        .unwrap();
+}
+
+struct Measurement {
+    pub s: f64, // True value
+    pub x: f64, // Measurement
+    pub t: f64, // Time
+    pub v: f64,
+}
+
+fn get_data() -> Vec<Measurement> {
+    let mut s = 121920.0;
+    let mut t = 0.0;
+    let mut u = -1828.8;
+    let dt = 0.1;
+
+    let mut m = vec![];
+
+    let normal = Normal::new(0.0, SIGNOISE).unwrap();
+    let mut rng = default_rng();
+
+    while s > 0.0 {
+        // Measurement
+        let mes = Measurement {
+            s: s,
+            t: t,
+            x: s + normal.sample(&mut rng),
+            v: u,
+        };
+        m.push(mes);
+
+        // Propagate
+        let v = u - G * dt; // For now, all measurements 1s apart
+        let d = 0.5 * (u + v) * dt; // Again, all measurements 1s apart
+
+        s += d;
+        u = v;
+        t += dt;
+    }
+
+    return m;
+}
+
+fn phi(dt: f64) -> Matrix {
+    let phi = matrix(vec![1.0, dt, 0.0, 1.0], 2, 2, Row);
+
+    return phi;
+}
+
+fn gravity(dt: f64) -> Matrix {
+    return matrix(vec![-0.5 * G * dt.powf(2.0), -G * dt], 2, 1, Row);
+}
+
+fn q(dt: f64) -> Matrix {
+    let mut q = zeros(2, 2);
+
+    q[(0, 0)] = dt.powf(3.0) / 3.0;
+    q[(0, 1)] = 0.5 * dt.powf(2.0);
+    q[(1, 0)] = q[(0, 1)];
+    q[(1, 1)] = dt;
+
+    return PHIS * q;
+}
@@ -1,6 +1,11 @@
-use kalman_filtering_rs::{make_k, make_m, new_cov};
+use kalman_filtering_rs::{
+    discretisation::van_loan,
+    integrators::rk4,
+    make_k, make_m, new_cov,
+    plotting::{estimate_figure, residual_figure, Figure, PlotOutput, Quantity, Series, TIME_AXIS},
+    rng::default_rng,
+};
 use peroxide::prelude::{matrix, zeros, Matrix, Shape::Row};
-use plotly::{common::Title, Layout, Plot, Scatter};
 use rand_distr::{Distribution, Normal};
 const G: f64 = -9.81;
 const BETA: f64 = 100.0; // Book suggests values from 2,500-10,000kg/m^2
@@ -19,8 +24,15 @@ fn main() {
     cov[(0, 0)] = 99999.9;
     cov[(1, 1)] = 99999.9;
 
+    let noise_input = matrix(vec![0.0, 1.0], 2, 1, Row);
+    let spectral_density = matrix(vec![PHI], 1, 1, Row);
+
     let mut x_history = vec![];
     let mut v_history = vec![];
+    let mut x_residual = vec![];
+    let mut v_residual = vec![];
+    let mut x_sigma = vec![];
+    let mut v_sigma = vec![];
     for i in 0..data.time.len() {
         let x_star = data.measured_positions_draggy[i];
 
@@ -33,8 +45,12 @@ fn main() {
         let xdot_bar = xdotkminus1 + TS * xdotdot_bar;
         let x_bar = xkminus1 + TS * xdot_bar;
 
-        let phi = make_phi(x_bar, xdot_bar);
-        let q = q(x_bar, xdot_bar);
+        let (phi, q) = van_loan(
+            &system_matrix(x_bar, xdot_bar),
+            &noise_input,
+            &spectral_density,
+            TS,
+        );
         let m = make_m(&phi, &cov, &q);
         let k = make_k(&m, &h, &r);
 
@@ -50,51 +66,65 @@ fn main() {
         cov = new_cov(&k, &h, &m);
         x_history.push(x_hat);
         v_history.push(-xdot_hat);
+
+        // The simulation counts velocity as positive downwards
+        x_residual.push(x_hat - data.draggy.position[i]);
+        v_residual.push(-xdot_hat - data.draggy.velocity[i]);
+        x_sigma.push(cov[(0, 0)].sqrt());
+        v_sigma.push(cov[(1, 1)].sqrt());
     }
 
-    // Position
-    let mut plot = Plot::new();
-
-    let trace_drag = Scatter::new(data.time.clone(), data.draggy.position.clone()).name("Drag");
-    let trace_nodrag =
-        Scatter::new(data.time.clone(), data.no_drag.position.clone()).name("No Drag");
-    let trace_measurements =
-        Scatter::new(data.time.clone(), data.measured_positions_draggy.clone())
-            .name("Measurements");
-    let trace_filter = Scatter::new(data.time.clone(), x_history).name("filter");
-    let layout = Layout::default().title(Title::new("Position"));
-    plot.set_layout(layout);
-    plot.add_traces(vec![
-        trace_drag,
-        trace_nodrag,
-        trace_measurements,
-        trace_filter,
-    ]);
-    plot.show();
-
-    // Velocity
-    let mut plot = Plot::new();
-
-    let trace_drag = Scatter::new(data.time.clone(), data.draggy.velocity.clone()).name("Drag");
-    let trace_nodrag =
-        Scatter::new(data.time.clone(), data.no_drag.velocity.clone()).name("No Drag");
-    let trace_history = Scatter::new(data.time.clone(), v_history).name("Filter");
-    let layout = Layout::default().title(Title::new("Velocity"));
-
-    plot.set_layout(layout);
-    plot.add_traces(vec![trace_drag, trace_nodrag, trace_history]);
-    plot.show();
-
-    // Acceleration
-    let mut plot = Plot::new();
-
-    let trace_drag = Scatter::new(data.time.clone(), data.draggy.acceleration.clone()).name("Drag");
-    let trace_nodrag =
-        Scatter::new(data.time.clone(), data.no_drag.acceleration.clone()).name("No Drag");
-    let layout = Layout::default().title(Title::new("Acceleration"));
-    plot.set_layout(layout);
-    plot.add_traces(vec![trace_drag, trace_nodrag]);
-    plot.show();
+    let position = Quantity::new("Position", "m");
+    let velocity = Quantity::new("Velocity", "m/s");
+
+    let mut position_plot = estimate_figure(
+        &data.time,
+        &x_history,
+        &position,
+        Some(data.draggy.position.as_slice()),
+        Some(data.measured_positions_draggy.as_slice()),
+    );
+    position_plot.add(Series::new(
+        "No Drag",
+        data.time.clone(),
+        data.no_drag.position.clone(),
+    ));
+
+    let mut velocity_plot = estimate_figure(
+        &data.time,
+        &v_history,
+        &velocity,
+        Some(data.draggy.velocity.as_slice()),
+        None,
+    );
+    velocity_plot.add(Series::new(
+        "No Drag",
+        data.time.clone(),
+        data.no_drag.velocity.clone(),
+    ));
+
+    let mut acceleration_plot = Figure::new("Acceleration", TIME_AXIS, "Acceleration (m/s^2)");
+    acceleration_plot.add(Series::new(
+        "Drag",
+        data.time.clone(),
+        data.draggy.acceleration.clone(),
+    ));
+    acceleration_plot.add(Series::new(
+        "No Drag",
+        data.time.clone(),
+        data.no_drag.acceleration.clone(),
+    ));
+
+    let output = PlotOutput::from_env();
+    position_plot.export("position", &output).unwrap();
+    velocity_plot.export("velocity", &output).unwrap();
+    acceleration_plot.export("acceleration", &output).unwrap();
+    residual_figure(&data.time, &x_residual, &x_sigma, &position)
+        .export("position-residual", &output)
+        .unwrap();
+    residual_figure(&data.time, &v_residual, &v_sigma, &velocity)
+        .export("velocity-residual", &output)
+        .unwrap();
 }
 
 fn get_data() -> SimulationData {
@@ -103,16 +133,20 @@ fn get_data() -> SimulationData {
     let mut v = 0.0;
     let a = G;
 
-    let mut x_drag = 6705.0;
-    let mut v_drag = 0.0;
-    let mut a_drag = G;
+    // Height and downwards speed, integrated with RK4 over each sample
+    let mut state_drag = matrix(vec![INITX, 0.0], 2, 1, Row);
+    let drag_dynamics = |_t: f64, state: &Matrix| {
+        let position = state[(0, 0)];
+        let velocity = state[(1, 0)];
+        return matrix(vec![-velocity, calc_accel(position, velocity)], 2, 1, Row);
+    };
 
     let mut data = SimulationData::default();
 
     let normal = Normal::new(0.0, SIGNOISE).unwrap();
-    let mut rng = rand::thread_rng();
+    let mut rng = default_rng();
 
-    while x_drag > 0.0 {
+    while state_drag[(0, 0)] > 0.0 {
         // No drag
         data.no_drag.position.push(x);
         data.no_drag.velocity.push(v);
@@ -125,23 +159,18 @@ fn get_data() -> SimulationData {
 
         x -= d;
         v = u;
-        t += TS;
 
         // Drag
+        let x_drag = state_drag[(0, 0)];
+        let v_drag = state_drag[(1, 0)];
         data.draggy.position.push(x_drag);
         data.draggy.velocity.push(v_drag);
-        data.draggy.acceleration.push(a_drag);
+        data.draggy.acceleration.push(-calc_accel(x_drag, v_drag));
         data.measured_positions_draggy
             .push(x_drag + normal.sample(&mut rng));
 
-        let tmp_a = -calc_accel(x_drag, v_drag);
-
-        let u = v_drag - tmp_a * TS;
-        let d = 0.5 * (u + v_drag) * TS;
-
-        x_drag -= d;
-        v_drag = u;
-        a_drag = tmp_a;
+        (state_drag, _) = rk4(&drag_dynamics, t, &state_drag, t + TS, 10);
+        t += TS;
     }
 
     return data;
@@ -172,14 +201,11 @@ struct SimulationUnit {
     pub acceleration: Vec<f64>,
 }
 
-fn make_phi(position: f64, velocity: f64) -> Matrix {
+// Linearised continuous dynamics, discretised exactly with Van Loan's method
+// rather than the first-order `I + F * TS`
+fn system_matrix(position: f64, velocity: f64) -> Matrix {
     return matrix(
-        vec![
-            1.0,
-            TS,
-            f21(position, velocity) * TS,
-            1.0 + f22(position, velocity) * TS,
-        ],
+        vec![0.0, 1.0, f21(position, velocity), f22(position, velocity)],
         2,
         2,
         Row,
@@ -197,19 +223,3 @@ fn f21(position: f64, velocity: f64) -> f64 {
 fn f22(position: f64, velocity: f64) -> f64 {
     return (rho(position) * velocity * G) / BETA;
 }
-
-fn q(position: f64, velocity: f64) -> Matrix {
-    let f22 = f22(position, velocity);
-    let q = matrix(
-        vec![
-            TS.powf(3.0) / 3.0,
-            TS.powf(2.0) / 2.0 + (f22 * TS.powf(3.0)) / 3.0,
-            TS.powf(2.0) / 2.0 + (f22 * TS.powf(3.0)) / 3.0,
-            TS + f22 * TS.powf(2.0) + f22.powf(2.0) * TS.powf(3.0) / 3.0,
-        ],
-        2,
-        2,
-        Row,
-    );
-    return PHI * q;
-}
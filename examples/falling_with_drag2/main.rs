@@ -1,8 +1,12 @@
+use kalman_filtering_rs::{
+    constraints::{truncate_pdf, Bound},
+    plotting::{estimate_figure, residual_figure, Figure, PlotOutput, Quantity, Series, TIME_AXIS},
+    rng::default_rng,
+};
 use peroxide::{
     fuga::LinearAlgebra,
     prelude::{eye, matrix, zeros, Matrix, Shape::Row},
 };
-use plotly::{common::Title, Layout, Plot, Scatter};
 use rand_distr::{Distribution, Normal};
 
 const G: f64 = -9.81;
@@ -11,6 +15,7 @@ const SIGNOISE: f64 = 304.8;
 const INITX: f64 = 6705.0;
 const PHI: f64 = 1.0;
 const BETA: f64 = 100.0;
+const MIN_BETA: f64 = 1.0;
 
 fn main() {
     let data = get_data();
@@ -27,6 +32,12 @@ fn main() {
     let mut x_history = vec![];
     let mut v_history = vec![];
     let mut beta_history = vec![];
+    let mut x_residual = vec![];
+    let mut v_residual = vec![];
+    let mut beta_residual = vec![];
+    let mut x_sigma = vec![];
+    let mut v_sigma = vec![];
+    let mut beta_sigma = vec![];
 
     for i in 0..data.time.len() {
         let x_star = data.measured_positions_draggy[i];
@@ -53,70 +64,90 @@ fn main() {
 
         println!("{}", state);
 
-        x_history.push(x_hat);
-        v_history.push(xdot_hat);
-        beta_history.push(beta_hat);
-
         state = matrix(vec![x_hat, xdot_hat, beta_hat], 3, 1, Row);
         cov = &(eye(3) - &k * &h) * &m;
+
+        // Nothing in the update stops beta going negative
+        (state, cov) = truncate_pdf(&state, &cov, &[Bound::at_least(2, MIN_BETA)]);
+
+        x_history.push(state.data[0]);
+        v_history.push(state.data[1]);
+        beta_history.push(state.data[2]);
+
+        // The simulation counts velocity as positive downwards
+        x_residual.push(state.data[0] - data.draggy.position[i]);
+        v_residual.push(-state.data[1] - data.draggy.velocity[i]);
+        beta_residual.push(state.data[2] - BETA);
+        x_sigma.push(cov[(0, 0)].sqrt());
+        v_sigma.push(cov[(1, 1)].sqrt());
+        beta_sigma.push(cov[(2, 2)].sqrt());
     }
 
-    // Position
-    let mut plot = Plot::new();
-
-    let trace_drag = Scatter::new(data.time.clone(), data.draggy.position.clone()).name("Drag");
-    let trace_nodrag =
-        Scatter::new(data.time.clone(), data.no_drag.position.clone()).name("No Drag");
-    let trace_measurements =
-        Scatter::new(data.time.clone(), data.measured_positions_draggy.clone())
-            .name("Measurements");
-    let trace_filter = Scatter::new(data.time.clone(), x_history).name("filter");
-    let layout = Layout::default().title(Title::new("Position"));
-    plot.set_layout(layout);
-    plot.add_traces(vec![
-        trace_drag,
-        trace_nodrag,
-        trace_measurements,
-        trace_filter,
-    ]);
-    plot.show();
-
-    // Velocity
-    let mut plot = Plot::new();
-
-    let trace_drag = Scatter::new(data.time.clone(), data.draggy.velocity.clone()).name("Drag");
-    let trace_nodrag =
-        Scatter::new(data.time.clone(), data.no_drag.velocity.clone()).name("No Drag");
-    let trace_history = Scatter::new(data.time.clone(), v_history).name("Filter");
-    let layout = Layout::default().title(Title::new("Velocity"));
-
-    plot.set_layout(layout);
-    plot.add_traces(vec![trace_drag, trace_nodrag, trace_history]);
-    plot.show();
-
-    // Acceleration
-    let mut plot = Plot::new();
-
-    let trace_drag = Scatter::new(data.time.clone(), data.draggy.acceleration.clone()).name("Drag");
-    let trace_nodrag =
-        Scatter::new(data.time.clone(), data.no_drag.acceleration.clone()).name("No Drag");
-    let layout = Layout::default().title(Title::new("Acceleration"));
-    plot.set_layout(layout);
-    plot.add_traces(vec![trace_drag, trace_nodrag]);
-    plot.show();
-
-    // Beta
-    let mut plot = Plot::new();
-    let layout = Layout::default().title(Title::new("Beta"));
-    let ideal = Scatter::new(
-        vec![data.time[0], data.time[data.time.len() - 1]],
-        vec![BETA, BETA],
-    )
-    .name("Ideal");
-    let beta_trace = Scatter::new(data.time.clone(), beta_history).name("Filter");
-    plot.set_layout(layout);
-    plot.add_traces(vec![ideal, beta_trace]);
-    plot.show();
+    let position = Quantity::new("Position", "m");
+    let velocity = Quantity::new("Velocity", "m/s");
+    let beta = Quantity::new("Beta", "kg/m^2");
+    let ideal_beta = vec![BETA; data.time.len()];
+
+    let mut position_plot = estimate_figure(
+        &data.time,
+        &x_history,
+        &position,
+        Some(data.draggy.position.as_slice()),
+        Some(data.measured_positions_draggy.as_slice()),
+    );
+    position_plot.add(Series::new(
+        "No Drag",
+        data.time.clone(),
+        data.no_drag.position.clone(),
+    ));
+
+    let mut velocity_plot = estimate_figure(
+        &data.time,
+        &v_history,
+        &velocity,
+        Some(data.draggy.velocity.as_slice()),
+        None,
+    );
+    velocity_plot.add(Series::new(
+        "No Drag",
+        data.time.clone(),
+        data.no_drag.velocity.clone(),
+    ));
+
+    let mut acceleration_plot = Figure::new("Acceleration", TIME_AXIS, "Acceleration (m/s^2)");
+    acceleration_plot.add(Series::new(
+        "Drag",
+        data.time.clone(),
+        data.draggy.acceleration.clone(),
+    ));
+    acceleration_plot.add(Series::new(
+        "No Drag",
+        data.time.clone(),
+        data.no_drag.acceleration.clone(),
+    ));
+
+    let beta_plot = estimate_figure(
+        &data.time,
+        &beta_history,
+        &beta,
+        Some(ideal_beta.as_slice()),
+        None,
+    );
+
+    let output = PlotOutput::from_env();
+    position_plot.export("position", &output).unwrap();
+    velocity_plot.export("velocity", &output).unwrap();
+    acceleration_plot.export("acceleration", &output).unwrap();
+    beta_plot.export("beta", &output).unwrap();
+    residual_figure(&data.time, &x_residual, &x_sigma, &position)
+        .export("position-residual", &output)
+        .unwrap();
+    residual_figure(&data.time, &v_residual, &v_sigma, &velocity)
+        .export("velocity-residual", &output)
+        .unwrap();
+    residual_figure(&data.time, &beta_residual, &beta_sigma, &beta)
+        .export("beta-residual", &output)
+        .unwrap();
 }
 
 fn get_data() -> SimulationData {
@@ -132,7 +163,7 @@ fn get_data() -> SimulationData {
     let mut data = SimulationData::default();
 
     let normal = Normal::new(0.0, SIGNOISE).unwrap();
-    let mut rng = rand::thread_rng();
+    let mut rng = default_rng();
 
     while x_drag > 0.0 {
         // No drag
@@ -0,0 +1,146 @@
+use kalman_filtering_rs::{
+    kalman_bucy::{solve_care, steady_state_gain, KalmanBucy},
+    make_k, make_m,
+    models::{ConstantVelocity, MotionModel},
+    new_cov,
+    plotting::{estimate_figure, residual_figure, Figure, PlotOutput, Quantity, Series, TIME_AXIS},
+    rng::default_rng,
+    Convergence,
+};
+use peroxide::prelude::{eye, matrix, Matrix, Shape::Row};
+use rand_distr::{Distribution, Normal};
+
+// Same target and process noise as the 1d_constant_velocity example, with the
+// measurement noise given as a spectral density so the discrete variance can
+// follow the sample time
+const SPEED: f64 = 2.0;
+const Q: f64 = 0.01;
+const R_C: f64 = 0.5;
+const SAMPLE_TIMES: [f64; 4] = [1.0, 0.1, 0.01, 0.001];
+const DT: f64 = 0.001;
+const DURATION: f64 = 60.0;
+
+fn main() {
+    let f = matrix(vec![0.0, 1.0, 0.0, 0.0], 2, 2, Row);
+    let h = matrix(vec![1.0, 0.0], 1, 2, Row);
+    let q = matrix(vec![0.0, 0.0, 0.0, Q], 2, 2, Row);
+    let r = matrix(vec![R_C], 1, 1, Row);
+
+    let convergence = Convergence::default();
+    let p_c = solve_care(&f, &h, &q, &r, &convergence).expect("Riccati equation did not converge");
+    let k_c = steady_state_gain(&f, &h, &q, &r, &convergence).unwrap();
+
+    println!(
+        "Kalman-Bucy: K = [{:.5}, {:.5}], P11 = {:.5}",
+        k_c[(0, 0)],
+        k_c[(1, 0)],
+        p_c[(0, 0)]
+    );
+
+    // The discrete gain scaled by the sample time tends to the continuous gain
+    for ts in SAMPLE_TIMES {
+        let (k_d, p_d) = discrete_steady_state(ts, &h);
+        println!(
+            "TS = {:>6}: K / TS = [{:.5}, {:.5}], P11 = {:.5}",
+            ts,
+            k_d[(0, 0)] / ts,
+            k_d[(1, 0)] / ts,
+            p_d[(0, 0)]
+        );
+    }
+
+    let data = get_data();
+    let mut filter = KalmanBucy::new(matrix(vec![3.0, 0.0], 2, 1, Row), eye(2), 0.0, f, h, q, r);
+
+    let mut t_history = vec![];
+    let mut k1_history = vec![];
+    let mut k2_history = vec![];
+    let mut v_history = vec![];
+    let mut v_residual = vec![];
+    let mut v_sigma = vec![];
+
+    for (t, z) in data.t.iter().zip(data.x_m.iter()) {
+        let k = filter.step(&matrix(vec![*z], 1, 1, Row), DT);
+        t_history.push(*t + DT);
+        k1_history.push(k[(0, 0)]);
+        k2_history.push(k[(1, 0)]);
+        v_history.push(filter.x[(1, 0)]);
+        v_residual.push(filter.x[(1, 0)] - SPEED);
+        v_sigma.push(filter.p[(1, 1)].sqrt());
+    }
+
+    let output = PlotOutput::from_env();
+
+    // Gains
+    let mut figure = Figure::new("Kalman-Bucy Gain", TIME_AXIS, "Gain (1/s)");
+    let ends = vec![t_history[0], t_history[t_history.len() - 1]];
+    figure.add(Series::new("K1", t_history.clone(), k1_history));
+    figure.add(Series::new("K2", t_history.clone(), k2_history));
+    figure.add(Series::new(
+        "Steady state K1",
+        ends.clone(),
+        vec![k_c[(0, 0)]; 2],
+    ));
+    figure.add(Series::new("Steady state K2", ends, vec![k_c[(1, 0)]; 2]));
+    figure.export("gain", &output).unwrap();
+
+    // Speed
+    let velocity = Quantity::new("Velocity", "m/s");
+    let speed = vec![SPEED; t_history.len()];
+    estimate_figure(
+        &t_history,
+        &v_history,
+        &velocity,
+        Some(speed.as_slice()),
+        None,
+    )
+    .export("velocity", &output)
+    .unwrap();
+    residual_figure(&t_history, &v_residual, &v_sigma, &velocity)
+        .export("velocity-residual", &output)
+        .unwrap();
+}
+
+fn discrete_steady_state(ts: f64, h: &Matrix) -> (Matrix, Matrix) {
+    let model = ConstantVelocity::new(1, Q);
+    let phi = model.phi(ts);
+    let q = model.q(ts);
+    let r = matrix(vec![R_C / ts], 1, 1, Row);
+
+    let mut p = eye(2);
+    let mut k = matrix(vec![0.0, 0.0], 2, 1, Row);
+    // Enough steps to cover several time constants at every sample time
+    for _ in 0..(100.0 / ts) as usize {
+        let m = make_m(&phi, &p, &q);
+        k = make_k(&m, h, &r);
+        p = new_cov(&k, h, &m);
+    }
+
+    return (k, p);
+}
+
+fn get_data() -> Data {
+    let mut ts = vec![];
+    let mut xms = vec![];
+    let mut x = 0.0;
+    let mut t = 0.0;
+
+    // White noise of spectral density R_C sampled every DT
+    let mut rng = default_rng();
+    let normal = Normal::new(0.0, (R_C / DT).sqrt()).unwrap();
+
+    while t < DURATION {
+        ts.push(t);
+        xms.push(x + normal.sample(&mut rng));
+
+        x += SPEED * DT;
+        t += DT;
+    }
+
+    return Data { t: ts, x_m: xms };
+}
+
+struct Data {
+    pub t: Vec<f64>,
+    pub x_m: Vec<f64>,
+}
@@ -0,0 +1,97 @@
+use kalman_filtering_rs::{
+    filter::KalmanFilter,
+    models::ConstantVelocity,
+    monte_carlo::{self, TrialStep},
+    plotting::{Figure, PlotOutput, Series, TIME_AXIS},
+    simulation::Simulation,
+};
+use peroxide::prelude::{eye, matrix, Shape::Row};
+
+const TRIALS: usize = 100;
+const SPEED: f64 = 2.0;
+const TS: f64 = 0.1;
+const DURATION: f64 = 30.0;
+const SIGMA: f64 = 5.0;
+const Q: f64 = 0.01;
+const CONFIDENCE: f64 = 0.95;
+
+fn main() {
+    let model = ConstantVelocity::new(1, Q);
+    let h = matrix(vec![1.0, 0.0], 1, 2, Row);
+    let r = matrix(vec![SIGMA.powf(2.0)], 1, 1, Row);
+
+    let simulation = Simulation::from_model(model, h.clone(), r.clone());
+    let x0 = matrix(vec![0.0, SPEED], 2, 1, Row);
+
+    let summary = monte_carlo::run(TRIALS, |_, rng| {
+        let data = simulation.run_for_with_rng(&x0, 0.0, DURATION, TS, rng);
+
+        let mut p = eye(2);
+        p[(0, 0)] = SIGMA.powf(2.0);
+        p[(1, 1)] = SPEED.powf(2.0);
+        let x = matrix(vec![data.measurements[0][(0, 0)], 0.0], 2, 1, Row);
+        let mut filter = KalmanFilter::from_model(x, p, 0.0, model, h.clone(), r.clone());
+
+        return data
+            .timestamped()
+            .iter()
+            .zip(data.truth.iter())
+            .skip(1)
+            .map(|((t, z), truth)| {
+                let record = filter.step(*t, z.as_ref());
+                TrialStep::from_record(truth.clone(), &record)
+            })
+            .collect();
+    });
+
+    // Every trial has steps and measurements, so none of these are `None`
+    let (nees_lower, nees_upper) = summary.average_nees_bounds(CONFIDENCE).unwrap();
+    let (nis_lower, nis_upper) = summary.average_nis_bounds(CONFIDENCE).unwrap();
+    println!(
+        "Average NEES {:.3} ({:.3} - {:.3}), {:.1}% of steps within bounds",
+        summary.average_nees().unwrap(),
+        nees_lower,
+        nees_upper,
+        100.0 * summary.nees_fraction_within(CONFIDENCE).unwrap()
+    );
+    println!(
+        "Average NIS {:.3} ({:.3} - {:.3}), {:.1}% of steps within bounds",
+        summary.average_nis().unwrap(),
+        nis_lower,
+        nis_upper,
+        100.0 * summary.nis_fraction_within(CONFIDENCE).unwrap()
+    );
+
+    let output = PlotOutput::from_env();
+
+    // RMS error against the filter's own standard deviation
+    for (state, name, unit) in [(0, "Position", "m"), (1, "Velocity", "m/s")] {
+        let mut figure = Figure::new(
+            &format!("{} Error over {} Trials", name, TRIALS),
+            TIME_AXIS,
+            &format!("{} ({})", name, unit),
+        );
+        figure.add(Series::new(
+            "RMS error",
+            summary.t.clone(),
+            summary.rms_error_of(state),
+        ));
+        figure.add(Series::new(
+            "Filter std",
+            summary.t.clone(),
+            summary.reported_std_of(state),
+        ));
+        figure
+            .export(&format!("{}-error", name.to_lowercase()), &output)
+            .unwrap();
+    }
+
+    // Averaged NEES
+    let (lower, upper) = summary.nees_bounds(CONFIDENCE).unwrap();
+    let ends = vec![summary.t[0], summary.t[summary.t.len() - 1]];
+    let mut figure = Figure::new("Averaged NEES", TIME_AXIS, "NEES");
+    figure.add(Series::new("NEES", summary.t.clone(), summary.nees.clone()));
+    figure.add(Series::new("Lower bound", ends.clone(), vec![lower; 2]));
+    figure.add(Series::new("Upper bound", ends, vec![upper; 2]));
+    figure.export("nees", &output).unwrap();
+}
@@ -1,12 +1,18 @@
-use kalman_filtering_rs::{make_k, make_m, new_cov};
+use kalman_filtering_rs::{
+    make_m,
+    plotting::{ellipse_series, Figure, PlotOutput, Series},
+    rng::default_rng,
+    sequential::sequential_update,
+};
 use peroxide::prelude::{matrix, zeros, Shape::Row};
-use plotly::{Plot, Scatter};
 use rand_distr::{Distribution, Normal};
 
 const TRUE_X: f64 = 5000.0;
 const TRUE_Y: f64 = 300.0;
 const R: f64 = 100.0;
 const TS: f64 = 1.0;
+const CONFIDENCE: f64 = 0.95;
+const ELLIPSE_STEPS: [usize; 3] = [9, 29, 99];
 
 fn main() {
     let data = get_data();
@@ -28,6 +34,7 @@ fn main() {
     let phi = matrix(vec![1.0, 0.0, 0.0, 1.0], 2, 2, Row);
     let r = matrix(vec![R, 0.0, 0.0, R], 2, 2, Row);
     let q = zeros(2, 2);
+    let mut ellipses = vec![];
 
     for i in 0..data.r1.len() {
         let r1 = data.r1[i];
@@ -71,39 +78,44 @@ fn main() {
         );
 
         let m = make_m(&phi, &cov, &q);
-        let k = make_k(&m, &h, &r);
 
-        let k11 = k[(0, 0)];
-        let k12 = k[(0, 1)];
-        let k21 = k[(1, 0)];
-        let k22 = k[(1, 1)];
+        // r is diagonal, so the two ranges can be applied one after the other
+        let innovation = matrix(vec![res1, res2], 2, 1, Row);
+        let (new_state, new_cov) = sequential_update(&state, &m, &h, &r, &innovation);
 
-        let x_hat = x_bar + k11 * res1 + k12 * res2;
-        let y_hat = y_bar + k21 * res1 + k22 * res2;
+        let x_hat = new_state[(0, 0)];
+        let y_hat = new_state[(1, 0)];
 
-        state = matrix(vec![x_hat, y_hat], 2, 1, Row);
-        cov = new_cov(&k, &h, &m);
+        state = new_state;
+        cov = new_cov;
+
+        if ELLIPSE_STEPS.contains(&i) {
+            ellipses.push(ellipse_series(&state, &cov, [0, 1], CONFIDENCE));
+        }
 
         x_filter.push(x_hat);
         y_filter.push(y_hat);
     }
 
-    let mut plot = Plot::new();
-
-    let ideal_ranges_trace =
-        Scatter::new(x_ideal_h, y_ideal_h).name("Calculated from ideal ranges");
-    plot.add_trace(ideal_ranges_trace);
-
-    let true_trace = Scatter::new(vec![TRUE_X], vec![TRUE_Y]).name("True position");
-    plot.add_trace(true_trace);
-
-    let noisy_trace = Scatter::new(x_m, y_m).name("Calculated from noisy measurements");
-    plot.add_trace(noisy_trace);
-
-    let filter_trace = Scatter::new(x_filter, y_filter).name("Filter");
-    plot.add_trace(filter_trace);
-
-    plot.show();
+    let mut figure = Figure::new("Receiver Position", "x (m)", "y (m)");
+    figure.add(Series::new(
+        "Calculated from ideal ranges",
+        x_ideal_h,
+        y_ideal_h,
+    ));
+    figure.add(Series::new("True position", vec![TRUE_X], vec![TRUE_Y]));
+    figure.add(Series::new("Calculated from noisy measurements", x_m, y_m));
+    figure.add(Series::new("Filter", x_filter, y_filter));
+
+    // The ellipses shrink as range measurements accumulate
+    for (i, ellipse) in ellipses.into_iter().enumerate() {
+        figure.add(if i == 0 {
+            ellipse
+        } else {
+            ellipse.without_legend()
+        });
+    }
+    figure.export("position", &PlotOutput::from_env()).unwrap();
 }
 
 fn solve_position(r1: f64, r2: f64, xr1: f64, yr1: f64, xr2: f64, yr2: f64) -> (f64, f64) {
@@ -144,7 +156,7 @@ fn get_data() -> Data {
     let mut yr2_h = vec![];
 
     let normal = Normal::new(0.0, R).unwrap();
-    let mut rng = rand::thread_rng();
+    let mut rng = default_rng();
 
     for i in 0..100 {
         let r1 = ((x1 - TRUE_X).powf(2.0) + (y1 - TRUE_Y).powf(2.0)).sqrt();
@@ -0,0 +1,263 @@
+use kalman_filtering_rs::{
+    filter::KalmanFilter,
+    fusion::{Sensor, SensorFusion, SensorMeasurement},
+    logs::{read_measurements_from_path, write_history_to_path, ColumnMapping},
+    rng::default_rng,
+};
+use peroxide::prelude::{matrix, zeros, Matrix, Shape::Row};
+use rand_distr::{Distribution, Normal};
+
+const DURATION: f64 = 60.0;
+const VX: f64 = 50.0; // m/s
+const VY: f64 = 5.0; // m/s
+const GPS_RATE: f64 = 1.0; // Hz
+const RADAR_RATE: f64 = 10.0; // Hz
+const ALTIMETER_RATE: f64 = 50.0; // Hz
+const GPS_ERROR: f64 = 10.0; // m
+const RADAR_R_ERROR: f64 = 20.0; // m
+const RADAR_THETA_ERROR: f64 = 0.005; // radians
+const ALTIMETER_ERROR: f64 = 2.0; // m
+const PHIS: f64 = 0.1;
+const WRITE: bool = false;
+
+fn main() {
+    let mut cov = zeros(4, 4);
+    cov[(0, 0)] = 99999.9;
+    cov[(1, 1)] = 99999.9;
+    cov[(2, 2)] = 99999.9;
+    cov[(3, 3)] = 99999.9;
+
+    let filter = KalmanFilter::new(
+        matrix(vec![0.0, VX, 1000.0, 0.0], 4, 1, Row),
+        cov,
+        0.0,
+        phi,
+        q,
+        zeros(1, 4),
+        zeros(1, 1),
+    );
+    let mut fusion = SensorFusion::new(filter);
+
+    let gps = fusion.add_sensor(Sensor::linear(
+        "GPS",
+        matrix(
+            vec![
+                1.0, 0.0, 0.0, 0.0, //
+                0.0, 0.0, 1.0, 0.0, //
+            ],
+            2,
+            4,
+            Row,
+        ),
+        matrix(
+            vec![GPS_ERROR.powf(2.0), 0.0, 0.0, GPS_ERROR.powf(2.0)],
+            2,
+            2,
+            Row,
+        ),
+        GPS_RATE,
+    ));
+    let radar = fusion.add_sensor(Sensor::non_linear(
+        "Radar",
+        radar_h,
+        radar_jacobian,
+        matrix(
+            vec![
+                RADAR_R_ERROR.powf(2.0),
+                0.0,
+                0.0,
+                RADAR_THETA_ERROR.powf(2.0),
+            ],
+            2,
+            2,
+            Row,
+        ),
+        RADAR_RATE,
+    ));
+    let altimeter = fusion.add_sensor(Sensor::linear(
+        "Altimeter",
+        matrix(vec![0.0, 0.0, 1.0, 0.0], 1, 4, Row),
+        matrix(vec![ALTIMETER_ERROR.powf(2.0)], 1, 1, Row),
+        ALTIMETER_RATE,
+    ));
+
+    // A measurement log given on the command line replaces the simulation
+    let measurements = match std::env::args().nth(1) {
+        Some(path) => read_log(&path, &fusion),
+        None => simulate(gps, radar, altimeter),
+    };
+
+    let history = fusion.process(measurements);
+    if WRITE {
+        write_history_to_path("sensor-fusion-history.csv", &history).unwrap();
+    }
+
+    let last = &history[history.len() - 1];
+    let (x, y) = truth(last.t);
+    println!(
+        "Final error x: {:.2} m, y: {:.2} m",
+        last.posterior[(0, 0)] - x,
+        last.posterior[(2, 0)] - y
+    );
+
+    for (sensor, diagnostics) in fusion.sensors.iter().zip(fusion.diagnostics.iter()) {
+        let average_nis = match diagnostics.average_nis() {
+            Some(value) => format!("{:.3}", value),
+            None => "-".to_string(),
+        };
+        let observed_rate = match diagnostics.observed_rate() {
+            Some(value) => format!("{:.1}", value),
+            None => "-".to_string(),
+        };
+        println!(
+            "{}: {} updates at {} Hz (nominal {:.1} Hz), average NIS {}, {} late",
+            sensor.name,
+            diagnostics.updates(),
+            observed_rate,
+            sensor.rate,
+            average_nis,
+            diagnostics.late
+        );
+    }
+}
+
+/// Reads rows of `t,sensor,z0,z1` with `sensor` one of `GPS`, `Radar` or
+/// `Altimeter`, and `z1` empty for the altimeter. Rows that name another
+/// sensor or have the wrong size for theirs are reported and skipped.
+fn read_log(path: &str, fusion: &SensorFusion) -> Vec<SensorMeasurement> {
+    let mapping = ColumnMapping::new("t", &["z0", "z1"]).with_sensor("sensor");
+    let records = read_measurements_from_path(path, &mapping).unwrap();
+
+    let mut measurements = vec![];
+    for record in records.iter() {
+        match record.to_sensor_measurement(fusion) {
+            Ok(Some(measurement)) => measurements.push(measurement),
+            Ok(None) => {}
+            Err(e) => eprintln!("Skipping {}", e),
+        }
+    }
+    return measurements;
+}
+
+fn simulate(gps: usize, radar: usize, altimeter: usize) -> Vec<SensorMeasurement> {
+    let mut rng = default_rng();
+    let gps_normal = Normal::new(0.0, GPS_ERROR).unwrap();
+    let r_normal = Normal::new(0.0, RADAR_R_ERROR).unwrap();
+    let theta_normal = Normal::new(0.0, RADAR_THETA_ERROR).unwrap();
+    let altimeter_normal = Normal::new(0.0, ALTIMETER_ERROR).unwrap();
+
+    let mut measurements = vec![];
+    for t in sample_times(GPS_RATE) {
+        let (x, y) = truth(t);
+        measurements.push(SensorMeasurement {
+            sensor: gps,
+            t,
+            z: matrix(
+                vec![
+                    x + gps_normal.sample(&mut rng),
+                    y + gps_normal.sample(&mut rng),
+                ],
+                2,
+                1,
+                Row,
+            ),
+            r: None,
+        });
+    }
+    for t in sample_times(RADAR_RATE) {
+        let (x, y) = truth(t);
+        let z = radar_h(&matrix(vec![x, 0.0, y, 0.0], 4, 1, Row));
+        measurements.push(SensorMeasurement {
+            sensor: radar,
+            t,
+            z: matrix(
+                vec![
+                    z[(0, 0)] + r_normal.sample(&mut rng),
+                    z[(1, 0)] + theta_normal.sample(&mut rng),
+                ],
+                2,
+                1,
+                Row,
+            ),
+            r: None,
+        });
+    }
+    for t in sample_times(ALTIMETER_RATE) {
+        let (_, y) = truth(t);
+        measurements.push(SensorMeasurement {
+            sensor: altimeter,
+            t,
+            z: matrix(vec![y + altimeter_normal.sample(&mut rng)], 1, 1, Row),
+            r: None,
+        });
+    }
+
+    return measurements;
+}
+
+fn sample_times(rate: f64) -> Vec<f64> {
+    let n = (DURATION * rate) as usize;
+    return (0..n).map(|i| i as f64 / rate).collect();
+}
+
+fn truth(t: f64) -> (f64, f64) {
+    return (VX * t, 1000.0 + VY * t);
+}
+
+fn radar_h(x: &Matrix) -> Matrix {
+    let px = x[(0, 0)];
+    let py = x[(2, 0)];
+    return matrix(
+        vec![(px.powf(2.0) + py.powf(2.0)).sqrt(), py.atan2(px)],
+        2,
+        1,
+        Row,
+    );
+}
+
+fn radar_jacobian(x: &Matrix) -> Matrix {
+    let px = x[(0, 0)];
+    let py = x[(2, 0)];
+    let r = (px.powf(2.0) + py.powf(2.0)).sqrt();
+    return matrix(
+        vec![
+            px / r,
+            0.0,
+            py / r,
+            0.0,
+            -py / r.powf(2.0),
+            0.0,
+            px / r.powf(2.0),
+            0.0,
+        ],
+        2,
+        4,
+        Row,
+    );
+}
+
+fn phi(dt: f64) -> Matrix {
+    return matrix(
+        vec![
+            1.0, dt, 0.0, 0.0, //
+            0.0, 1.0, 0.0, 0.0, //
+            0.0, 0.0, 1.0, dt, //
+            0.0, 0.0, 0.0, 1.0, //
+        ],
+        4,
+        4,
+        Row,
+    );
+}
+
+fn q(dt: f64) -> Matrix {
+    let mut q = zeros(4, 4);
+    for axis in 0..2 {
+        let i = 2 * axis;
+        q[(i, i)] = dt.powf(3.0) / 3.0;
+        q[(i, i + 1)] = dt.powf(2.0) / 2.0;
+        q[(i + 1, i)] = dt.powf(2.0) / 2.0;
+        q[(i + 1, i + 1)] = dt;
+    }
+    return PHIS * q;
+}
@@ -1,26 +1,23 @@
 use std::ops::Div;
 
-use kalman_filtering_rs::{make_k, make_m, new_cov, write_to_file};
+use kalman_filtering_rs::{
+    integrators::Rk45, make_k, make_m, new_cov, plotting::PlotOutput, write_to_file,
+};
 use peroxide::prelude::{matrix, zeros, Matrix, Shape::Row};
-use plotly::{common::Title, Layout, Plot, Scatter};
 
-use crate::{get_data, OMEGA, R, TS, WRITE};
+use crate::{get_data, sin_wave_figures, OMEGA, R, TS, WRITE};
 
 const NON_LINEAR_ALTERNATIVE_Q: f64 = 10.0;
 
 pub fn alternative_non_linear() {
-    fn project(x: f64, x_dot: f64, omega: f64, step: f64) -> (f64, f64) {
-        let mut x_bar = x;
-        let mut x_dot_bar = x_dot;
-        let mut t = 0.0;
-        while t <= TS {
-            let x_dot_dot = -omega.powf(2.0) * x_bar;
-            x_dot_bar = x_dot_bar + step * x_dot_dot;
-            x_bar = x_bar + step * x_dot_bar;
-            t = t + step;
-        }
-
-        return (x_bar, x_dot_bar);
+    fn project(x: f64, x_dot: f64, omega: f64) -> (f64, f64) {
+        let oscillator =
+            |_t: f64, s: &Matrix| matrix(vec![s[(1, 0)], -omega.powf(2.0) * s[(0, 0)]], 2, 1, Row);
+        let (projected, _) = Rk45::default()
+            .integrate(oscillator, 0.0, &matrix(vec![x, x_dot], 2, 1, Row), TS)
+            .unwrap();
+
+        return (projected[(0, 0)], projected[(1, 0)]);
     }
 
     let data = get_data();
@@ -35,8 +32,7 @@ pub fn alternative_non_linear() {
 
     let mut x_history = vec![];
 
-    let mut measurement_residuals = vec![];
-    let mut filter_residuals = vec![];
+    let mut sigma = vec![];
 
     for i in 0..data.t.len() {
         let xkminus1 = state.data[0];
@@ -64,7 +60,7 @@ pub fn alternative_non_linear() {
         let m = make_m(&phi, &cov, &q);
         let k = make_k(&m, &h, &r);
 
-        let (x_bar, x_dot_bar) = project(xkminus1, xdotkminus1, omegaminus1, 1e-5);
+        let (x_bar, x_dot_bar) = project(xkminus1, xdotkminus1, omegaminus1);
 
         let x_star = data.y_m[i];
 
@@ -79,50 +75,36 @@ pub fn alternative_non_linear() {
         let omega_hat = omegaminus1 + k3 * x_tilde;
 
         x_history.push(x_hat);
-        filter_residuals.push(x_hat - data.y[i]);
-        measurement_residuals.push(x_star - data.y[i]);
 
         state = matrix(vec![x_hat, x_dot_hat, omega_hat], 3, 1, Row);
         cov = new_cov(&k, &h, &m);
+        sigma.push(cov[(0, 0)].sqrt());
     }
 
-    // Sin Wave Plot
-    let mut full_plot = Plot::new();
-    let ideal_trace = Scatter::new(data.t.clone(), data.y.clone()).name("Theory");
-    full_plot.add_trace(ideal_trace);
+    let (full_plot, residual_plot) =
+        sin_wave_figures("Non-Linear Alternative", &data, &x_history, &sigma);
 
-    let measurement_trace = Scatter::new(data.t.clone(), data.y_m.clone()).name("Measurements");
-    full_plot.add_trace(measurement_trace);
-
-    let filter_trace = Scatter::new(data.t.clone(), x_history).name("x");
-    full_plot.add_trace(filter_trace);
-
-    let layout = Layout::default().title(Title::new("Non-Linear Alternative"));
-    full_plot.set_layout(layout);
-    full_plot.show();
-
-    // Residuals
-    let mut residual_plot = Plot::new();
-    let measurement_trace =
-        Scatter::new(data.t.clone(), measurement_residuals).name("Measurements");
-    residual_plot.add_trace(measurement_trace);
-
-    let filter_trace = Scatter::new(data.t.clone(), filter_residuals).name("x");
-    residual_plot.add_trace(filter_trace);
-
-    let layout = Layout::default().title(Title::new("Non-Linear Alternative Residuals"));
-    residual_plot.set_layout(layout);
-    residual_plot.show();
+    let output = PlotOutput::from_env();
+    full_plot
+        .export("full-plot-alternative-non-linear", &output)
+        .unwrap();
+    residual_plot
+        .export("residual-alternative-non-linear", &output)
+        .unwrap();
 
     if WRITE {
         let namespace = "alternative-non-linear".to_string();
         write_to_file(
             &format!("full-plot-{}.html.tera", namespace),
-            &full_plot.to_inline_html("full-plot-alternative-non-linear"),
+            &full_plot
+                .to_plotly()
+                .to_inline_html("full-plot-alternative-non-linear"),
         );
         write_to_file(
             &format!("residual-{}.html.tera", namespace),
-            &residual_plot.to_inline_html("residual-alternative-non-linear"),
+            &residual_plot
+                .to_plotly()
+                .to_inline_html("residual-alternative-non-linear"),
         );
     }
 }
@@ -1,8 +1,7 @@
-use kalman_filtering_rs::{make_k, make_m, new_cov, write_to_file};
+use kalman_filtering_rs::{make_k, make_m, new_cov, plotting::PlotOutput, write_to_file};
 use peroxide::prelude::{matrix, zeros, Shape::Row};
-use plotly::{common::Title, Layout, Plot, Scatter};
 
-use crate::{get_data, q_linear_first_order, R, TS, WRITE};
+use crate::{get_data, q_linear_first_order, sin_wave_figures, R, TS, WRITE};
 
 pub fn linear_first_order() {
     let data = get_data();
@@ -20,8 +19,7 @@ pub fn linear_first_order() {
 
     let mut x_filter = vec![];
 
-    let mut measurement_residuals = vec![];
-    let mut filter_residuals = vec![];
+    let mut sigma = vec![];
 
     for i in 0..data.t.len() {
         let x_star = data.y_m[i];
@@ -38,50 +36,36 @@ pub fn linear_first_order() {
         let x_dot_hat = xdotkminus1 + k[(1, 0)] * x_tilde;
 
         x_filter.push(x_hat);
-        measurement_residuals.push(x_star - data.y[i]);
-        filter_residuals.push(x_hat - data.y[i]);
 
         state = matrix(vec![x_hat, x_dot_hat], 2, 1, Row);
         cov = new_cov(&k, &h, &m);
+        sigma.push(cov[(0, 0)].sqrt());
     }
 
-    // Sin Wave Plot
-    let mut full_plot = Plot::new();
-    let ideal_trace = Scatter::new(data.t.clone(), data.y.clone()).name("Theory");
-    full_plot.add_trace(ideal_trace);
+    let (full_plot, residual_plot) =
+        sin_wave_figures("Linear First Order", &data, &x_filter, &sigma);
 
-    let measurement_trace = Scatter::new(data.t.clone(), data.y_m.clone()).name("Measurements");
-    full_plot.add_trace(measurement_trace);
-
-    let filter_trace = Scatter::new(data.t.clone(), x_filter.clone()).name("Filter");
-    full_plot.add_trace(filter_trace);
-
-    let layout = Layout::default().title(Title::new("Linear First Order"));
-    full_plot.set_layout(layout);
-    full_plot.show();
-
-    // Residuals
-    let mut residual_plot = Plot::new();
-    let measurement_trace =
-        Scatter::new(data.t.clone(), measurement_residuals).name("Measurements");
-    residual_plot.add_trace(measurement_trace);
-
-    let filter_trace = Scatter::new(data.t.clone(), filter_residuals).name("Filter");
-    residual_plot.add_trace(filter_trace);
-
-    let layout = Layout::default().title(Title::new("Linear First Order Residuals"));
-    residual_plot.set_layout(layout);
-    residual_plot.show();
+    let output = PlotOutput::from_env();
+    full_plot
+        .export("full-plot-linear-first-order", &output)
+        .unwrap();
+    residual_plot
+        .export("residual-linear-first-order", &output)
+        .unwrap();
 
     if WRITE {
         let namespace = "linear-first-order".to_string();
         write_to_file(
             &format!("full-plot-{}.html.tera", namespace),
-            &full_plot.to_inline_html("full-plot-linear-first-order"),
+            &full_plot
+                .to_plotly()
+                .to_inline_html("full-plot-linear-first-order"),
         );
         write_to_file(
             &format!("residual-{}.html.tera", namespace),
-            &residual_plot.to_inline_html("residual-linear-first-order"),
+            &residual_plot
+                .to_plotly()
+                .to_inline_html("residual-linear-first-order"),
         );
     }
 }
@@ -1,8 +1,7 @@
-use kalman_filtering_rs::{make_k, make_m, new_cov, write_to_file};
+use kalman_filtering_rs::{make_k, make_m, new_cov, plotting::PlotOutput, write_to_file};
 use peroxide::prelude::{matrix, zeros, Matrix, Shape::Row};
-use plotly::{common::Title, Layout, Plot, Scatter};
 
-use crate::{get_data, R, TS, WRITE};
+use crate::{get_data, sin_wave_figures, R, TS, WRITE};
 
 const LINEAR_SECOND_ORDER_Q: f64 = 10.0;
 
@@ -27,8 +26,7 @@ pub fn linear_second_order() {
 
     let mut filter_history = vec![];
 
-    let mut measurement_residuals = vec![];
-    let mut filter_residuals = vec![];
+    let mut sigma = vec![];
 
     for i in 0..data.t.len() {
         let x_star = data.y_m[i];
@@ -52,50 +50,36 @@ pub fn linear_second_order() {
         let x_dot_dot_hat = xdotdotkminus1 + k3 * x_tilde;
 
         filter_history.push(x_hat);
-        filter_residuals.push(x_hat - data.y[i]);
-        measurement_residuals.push(x_star - data.y[i]);
 
         state = matrix(vec![x_hat, x_dot_hat, x_dot_dot_hat], 3, 1, Row);
         cov = new_cov(&k, &h, &m);
+        sigma.push(cov[(0, 0)].sqrt());
     }
 
-    // Sin Wave Plot
-    let mut full_plot = Plot::new();
-    let ideal_trace = Scatter::new(data.t.clone(), data.y.clone()).name("Theory");
-    full_plot.add_trace(ideal_trace);
+    let (full_plot, residual_plot) =
+        sin_wave_figures("Linear Second Order", &data, &filter_history, &sigma);
 
-    let measurement_trace = Scatter::new(data.t.clone(), data.y_m.clone()).name("Measurements");
-    full_plot.add_trace(measurement_trace);
-
-    let filter_trace = Scatter::new(data.t.clone(), filter_history.clone()).name("Filter");
-    full_plot.add_trace(filter_trace);
-
-    let layout = Layout::default().title(Title::new("Linear Second Order"));
-    full_plot.set_layout(layout);
-    full_plot.show();
-
-    // Residuals
-    let mut residual_plot = Plot::new();
-    let measurement_trace =
-        Scatter::new(data.t.clone(), measurement_residuals).name("Measurements");
-    residual_plot.add_trace(measurement_trace);
-
-    let filter_trace = Scatter::new(data.t.clone(), filter_residuals).name("Filter");
-    residual_plot.add_trace(filter_trace);
-
-    let layout = Layout::default().title(Title::new("Linear Second Order Residuals"));
-    residual_plot.set_layout(layout);
-    residual_plot.show();
+    let output = PlotOutput::from_env();
+    full_plot
+        .export("full-plot-linear-second-order", &output)
+        .unwrap();
+    residual_plot
+        .export("residual-linear-second-order", &output)
+        .unwrap();
 
     if WRITE {
         let namespace = "linear-second-order".to_string();
         write_to_file(
             &format!("full-plot-{}.html.tera", namespace),
-            &full_plot.to_inline_html("full-plot-linear-second-order"),
+            &full_plot
+                .to_plotly()
+                .to_inline_html("full-plot-linear-second-order"),
         );
         write_to_file(
             &format!("residual-{}.html.tera", namespace),
-            &residual_plot.to_inline_html("residual-linear-second-order"),
+            &residual_plot
+                .to_plotly()
+                .to_inline_html("residual-linear-second-order"),
         );
     }
 }
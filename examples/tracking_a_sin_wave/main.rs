@@ -1,4 +1,8 @@
 use alternative_non_linear::alternative_non_linear;
+use kalman_filtering_rs::{
+    plotting::{estimate_figure, residual_figure, Figure, Quantity, Series},
+    rng::default_rng,
+};
 use linear_a_priori::linear_a_priori;
 use linear_first_order::linear_first_order;
 use linear_second_order::linear_second_order;
@@ -38,7 +42,7 @@ pub fn get_data() -> Data {
     let mut t = 0.0;
 
     let normal = Normal::new(0.0, R).unwrap();
-    let mut rng = rand::thread_rng();
+    let mut rng = default_rng();
 
     for _ in 0..1000 {
         let y = A * (OMEGA * t).sin();
@@ -63,6 +67,48 @@ pub struct Data {
     pub y_m: Vec<f64>,
 }
 
+/// The filter's estimate of the wave against the truth and measurements, and
+/// its residual inside the `sigma` envelopes with the measurement residual for
+/// comparison
+pub fn sin_wave_figures(
+    title: &str,
+    data: &Data,
+    estimate: &[f64],
+    sigma: &[f64],
+) -> (Figure, Figure) {
+    let quantity = Quantity::new("x", "m");
+
+    let mut full_plot = estimate_figure(
+        &data.t,
+        estimate,
+        &quantity,
+        Some(data.y.as_slice()),
+        Some(data.y_m.as_slice()),
+    );
+    full_plot.title = title.to_string();
+
+    let residual: Vec<f64> = estimate
+        .iter()
+        .zip(data.y.iter())
+        .map(|(estimate, y)| estimate - y)
+        .collect();
+    let measurement_residual: Vec<f64> = data
+        .y_m
+        .iter()
+        .zip(data.y.iter())
+        .map(|(y_m, y)| y_m - y)
+        .collect();
+    let mut residual_plot = residual_figure(&data.t, &residual, sigma, &quantity);
+    residual_plot.title = format!("{} Residuals", title);
+    residual_plot.add(Series::new(
+        "Measurements",
+        data.t.clone(),
+        measurement_residual,
+    ));
+
+    return (full_plot, residual_plot);
+}
+
 fn q_linear_first_order(dt: f64) -> Matrix {
     return LINEAR_FIRST_ORDER_Q
         * matrix(
@@ -1,8 +1,11 @@
-use kalman_filtering_rs::{make_k, make_m, new_cov, write_to_file};
+use kalman_filtering_rs::{
+    make_k, make_m, new_cov,
+    plotting::{PlotOutput, Series},
+    write_to_file,
+};
 use peroxide::prelude::{matrix, zeros, Matrix, Shape::Row};
-use plotly::{common::Title, Layout, Plot, Scatter};
 
-use crate::{get_data, A, OMEGA, R, TS, WRITE};
+use crate::{get_data, sin_wave_figures, A, OMEGA, R, TS, WRITE};
 
 const NON_LINEAR_A_PRIORI_Q: f64 = 10.0;
 
@@ -20,9 +23,8 @@ pub fn non_linear_a_priori() {
     let mut x_from_phi_history = vec![];
     let mut x_from_omega_history = vec![];
 
-    let mut measurement_residuals = vec![];
-    let mut x_from_phi_residuals = vec![];
     let mut x_from_omega_residuals = vec![];
+    let mut sigma = vec![];
 
     for i in 0..data.t.len() {
         let x_star = data.y_m[i];
@@ -52,57 +54,50 @@ pub fn non_linear_a_priori() {
         x_from_phi_history.push(x_from_phi);
         x_from_omega_history.push(x_from_omega);
 
-        measurement_residuals.push(x_star - data.y[i]);
-        x_from_phi_residuals.push(x_from_phi - data.y[i]);
         x_from_omega_residuals.push(x_from_omega - data.y[i]);
 
         state = matrix(vec![phi_hat, omega_hat], 2, 1, Row);
         cov = new_cov(&k, &h, &m);
-    }
-
-    // Sin Wave Plot
-    let mut full_plot = Plot::new();
-    let ideal_trace = Scatter::new(data.t.clone(), data.y.clone()).name("Theory");
-    full_plot.add_trace(ideal_trace);
-
-    let measurement_trace = Scatter::new(data.t.clone(), data.y_m.clone()).name("Measurements");
-    full_plot.add_trace(measurement_trace);
-
-    let filter_trace = Scatter::new(data.t.clone(), x_from_phi_history).name("x from phi");
-    full_plot.add_trace(filter_trace);
-
-    let filter_trace = Scatter::new(data.t.clone(), x_from_omega_history).name("x from omega");
-    full_plot.add_trace(filter_trace);
 
-    let layout = Layout::default().title(Title::new("Non-Linear A Priori"));
-    full_plot.set_layout(layout);
-    full_plot.show();
-
-    // Residuals
-    let mut residual_plot = Plot::new();
-    let measurement_trace =
-        Scatter::new(data.t.clone(), measurement_residuals).name("Measurements");
-    residual_plot.add_trace(measurement_trace);
-
-    let filter_trace = Scatter::new(data.t.clone(), x_from_omega_residuals).name("x from omega");
-    residual_plot.add_trace(filter_trace);
-
-    let filter_trace = Scatter::new(data.t.clone(), x_from_phi_residuals).name("x from phi");
-    residual_plot.add_trace(filter_trace);
+        // Uncertainty of x from phi, linearised about the posterior
+        let h_hat = matrix(vec![A * phi_hat.cos(), 0.0], 1, 2, Row);
+        sigma.push((&(&h_hat * &cov) * &h_hat.t())[(0, 0)].sqrt());
+    }
 
-    let layout = Layout::default().title(Title::new("Non-Linear A Priori Residuals"));
-    residual_plot.set_layout(layout);
-    residual_plot.show();
+    let (mut full_plot, mut residual_plot) =
+        sin_wave_figures("Non-Linear A Priori", &data, &x_from_phi_history, &sigma);
+    full_plot.add(Series::new(
+        "x from omega",
+        data.t.clone(),
+        x_from_omega_history,
+    ));
+    residual_plot.add(Series::new(
+        "x from omega",
+        data.t.clone(),
+        x_from_omega_residuals,
+    ));
+
+    let output = PlotOutput::from_env();
+    full_plot
+        .export("full-plot-non-linear-a-priori", &output)
+        .unwrap();
+    residual_plot
+        .export("residual-non-linear-a-priori", &output)
+        .unwrap();
 
     if WRITE {
         let namespace = "non-linear-a-priori".to_string();
         write_to_file(
             &format!("full-plot-{}.html.tera", namespace),
-            &full_plot.to_inline_html("full-plot-non-linear-a-priori"),
+            &full_plot
+                .to_plotly()
+                .to_inline_html("full-plot-non-linear-a-priori"),
         );
         write_to_file(
             &format!("residual-{}.html.tera", namespace),
-            &residual_plot.to_inline_html("residual-non-linear-a-priori"),
+            &residual_plot
+                .to_plotly()
+                .to_inline_html("residual-non-linear-a-priori"),
         );
     }
 }
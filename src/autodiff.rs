@@ -0,0 +1,336 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use peroxide::prelude::{zeros, Matrix};
+
+/// Hyper-dual number `re + e1 ε1 + e2 ε2 + e12 ε1 ε2` with `ε1² = ε2² = 0`.
+/// Evaluating a function with `ε1` seeded on `x_i` and `ε2` on `x_j` gives
+/// the exact first derivatives in `e1` and `e2` and the second derivative
+/// `d²f / dx_i dx_j` in `e12`, with no truncation error.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HyperDual {
+    pub re: f64,
+    pub e1: f64,
+    pub e2: f64,
+    pub e12: f64,
+}
+
+impl HyperDual {
+    pub fn new(re: f64, e1: f64, e2: f64, e12: f64) -> HyperDual {
+        return HyperDual { re, e1, e2, e12 };
+    }
+
+    pub fn constant(re: f64) -> HyperDual {
+        return HyperDual::new(re, 0.0, 0.0, 0.0);
+    }
+
+    /// Applies a scalar function given its value and first two derivatives
+    /// at `self.re`
+    fn chain(self, f: f64, df: f64, d2f: f64) -> HyperDual {
+        return HyperDual {
+            re: f,
+            e1: df * self.e1,
+            e2: df * self.e2,
+            e12: df * self.e12 + d2f * self.e1 * self.e2,
+        };
+    }
+
+    pub fn sin(self) -> HyperDual {
+        return self.chain(self.re.sin(), self.re.cos(), -self.re.sin());
+    }
+
+    pub fn cos(self) -> HyperDual {
+        return self.chain(self.re.cos(), -self.re.sin(), -self.re.cos());
+    }
+
+    pub fn exp(self) -> HyperDual {
+        let e = self.re.exp();
+        return self.chain(e, e, e);
+    }
+
+    pub fn ln(self) -> HyperDual {
+        return self.chain(self.re.ln(), 1.0 / self.re, -1.0 / self.re.powf(2.0));
+    }
+
+    pub fn sqrt(self) -> HyperDual {
+        let s = self.re.sqrt();
+        return self.chain(s, 0.5 / s, -0.25 / (s * self.re));
+    }
+
+    pub fn powf(self, n: f64) -> HyperDual {
+        return self.chain(
+            self.re.powf(n),
+            n * self.re.powf(n - 1.0),
+            n * (n - 1.0) * self.re.powf(n - 2.0),
+        );
+    }
+
+    pub fn atan(self) -> HyperDual {
+        let d = 1.0 + self.re.powf(2.0);
+        return self.chain(self.re.atan(), 1.0 / d, -2.0 * self.re / d.powf(2.0));
+    }
+
+    /// Four quadrant arctangent of `self / x`
+    pub fn atan2(self, x: HyperDual) -> HyperDual {
+        let y = self;
+        let rho = x.re.powf(2.0) + y.re.powf(2.0);
+
+        let g_y = x.re / rho;
+        let g_x = -y.re / rho;
+        let g_yy = -2.0 * x.re * y.re / rho.powf(2.0);
+        let g_xx = -g_yy;
+        let g_xy = (y.re.powf(2.0) - x.re.powf(2.0)) / rho.powf(2.0);
+
+        return HyperDual {
+            re: y.re.atan2(x.re),
+            e1: g_y * y.e1 + g_x * x.e1,
+            e2: g_y * y.e2 + g_x * x.e2,
+            e12: g_y * y.e12
+                + g_x * x.e12
+                + g_yy * y.e1 * y.e2
+                + g_xx * x.e1 * x.e2
+                + g_xy * (y.e1 * x.e2 + x.e1 * y.e2),
+        };
+    }
+}
+
+impl Add for HyperDual {
+    type Output = HyperDual;
+
+    fn add(self, rhs: HyperDual) -> HyperDual {
+        return HyperDual::new(
+            self.re + rhs.re,
+            self.e1 + rhs.e1,
+            self.e2 + rhs.e2,
+            self.e12 + rhs.e12,
+        );
+    }
+}
+
+impl Sub for HyperDual {
+    type Output = HyperDual;
+
+    fn sub(self, rhs: HyperDual) -> HyperDual {
+        return HyperDual::new(
+            self.re - rhs.re,
+            self.e1 - rhs.e1,
+            self.e2 - rhs.e2,
+            self.e12 - rhs.e12,
+        );
+    }
+}
+
+impl Mul for HyperDual {
+    type Output = HyperDual;
+
+    fn mul(self, rhs: HyperDual) -> HyperDual {
+        return HyperDual::new(
+            self.re * rhs.re,
+            self.re * rhs.e1 + self.e1 * rhs.re,
+            self.re * rhs.e2 + self.e2 * rhs.re,
+            self.re * rhs.e12 + self.e1 * rhs.e2 + self.e2 * rhs.e1 + self.e12 * rhs.re,
+        );
+    }
+}
+
+impl Div for HyperDual {
+    type Output = HyperDual;
+
+    fn div(self, rhs: HyperDual) -> HyperDual {
+        let inverse = rhs.chain(
+            1.0 / rhs.re,
+            -1.0 / rhs.re.powf(2.0),
+            2.0 / rhs.re.powf(3.0),
+        );
+        return self * inverse;
+    }
+}
+
+impl Neg for HyperDual {
+    type Output = HyperDual;
+
+    fn neg(self) -> HyperDual {
+        return HyperDual::new(-self.re, -self.e1, -self.e2, -self.e12);
+    }
+}
+
+impl Add<f64> for HyperDual {
+    type Output = HyperDual;
+
+    fn add(self, rhs: f64) -> HyperDual {
+        return self + HyperDual::constant(rhs);
+    }
+}
+
+impl Sub<f64> for HyperDual {
+    type Output = HyperDual;
+
+    fn sub(self, rhs: f64) -> HyperDual {
+        return self - HyperDual::constant(rhs);
+    }
+}
+
+impl Mul<f64> for HyperDual {
+    type Output = HyperDual;
+
+    fn mul(self, rhs: f64) -> HyperDual {
+        return HyperDual::new(self.re * rhs, self.e1 * rhs, self.e2 * rhs, self.e12 * rhs);
+    }
+}
+
+impl Div<f64> for HyperDual {
+    type Output = HyperDual;
+
+    fn div(self, rhs: f64) -> HyperDual {
+        return HyperDual::new(self.re / rhs, self.e1 / rhs, self.e2 / rhs, self.e12 / rhs);
+    }
+}
+
+impl Add<HyperDual> for f64 {
+    type Output = HyperDual;
+
+    fn add(self, rhs: HyperDual) -> HyperDual {
+        return rhs + self;
+    }
+}
+
+impl Sub<HyperDual> for f64 {
+    type Output = HyperDual;
+
+    fn sub(self, rhs: HyperDual) -> HyperDual {
+        return HyperDual::constant(self) - rhs;
+    }
+}
+
+impl Mul<HyperDual> for f64 {
+    type Output = HyperDual;
+
+    fn mul(self, rhs: HyperDual) -> HyperDual {
+        return rhs * self;
+    }
+}
+
+impl Div<HyperDual> for f64 {
+    type Output = HyperDual;
+
+    fn div(self, rhs: HyperDual) -> HyperDual {
+        return HyperDual::constant(self) / rhs;
+    }
+}
+
+/// Evaluates the vector function `f` at the column `x` with `ε1` seeded on
+/// `x_i` and `ε2` on `x_j`
+fn seeded<F>(f: &F, x: &Matrix, i: usize, j: usize) -> Vec<HyperDual>
+where
+    F: Fn(&[HyperDual]) -> Vec<HyperDual>,
+{
+    let arguments: Vec<HyperDual> = (0..x.row)
+        .map(|k| {
+            let e1 = if k == i { 1.0 } else { 0.0 };
+            let e2 = if k == j { 1.0 } else { 0.0 };
+            HyperDual::new(x[(k, 0)], e1, e2, 0.0)
+        })
+        .collect();
+    return f(&arguments);
+}
+
+/// Value of the vector function `f` at `x` as a column
+pub fn value<F>(f: &F, x: &Matrix) -> Matrix
+where
+    F: Fn(&[HyperDual]) -> Vec<HyperDual>,
+{
+    let arguments: Vec<HyperDual> = (0..x.row).map(|k| HyperDual::constant(x[(k, 0)])).collect();
+    let outputs = f(&arguments);
+
+    let mut out = zeros(outputs.len(), 1);
+    for (row, output) in outputs.iter().enumerate() {
+        out[(row, 0)] = output.re;
+    }
+    return out;
+}
+
+/// Jacobian of the vector function `f` at `x`
+pub fn jacobian<F>(f: &F, x: &Matrix) -> Matrix
+where
+    F: Fn(&[HyperDual]) -> Vec<HyperDual>,
+{
+    let mut out = zeros(0, 0);
+    for j in 0..x.row {
+        let outputs = seeded(f, x, j, j);
+        if j == 0 {
+            out = zeros(outputs.len(), x.row);
+        }
+        for (row, output) in outputs.iter().enumerate() {
+            out[(row, j)] = output.e1;
+        }
+    }
+    return out;
+}
+
+/// Hessian of each component of the vector function `f` at `x`
+pub fn hessians<F>(f: &F, x: &Matrix) -> Vec<Matrix>
+where
+    F: Fn(&[HyperDual]) -> Vec<HyperDual>,
+{
+    let n = x.row;
+    let mut out: Vec<Matrix> = vec![];
+    for i in 0..n {
+        for j in i..n {
+            let outputs = seeded(f, x, i, j);
+            if out.is_empty() {
+                out = vec![zeros(n, n); outputs.len()];
+            }
+            for (hessian, output) in out.iter_mut().zip(outputs.iter()) {
+                hessian[(i, j)] = output.e12;
+                hessian[(j, i)] = output.e12;
+            }
+        }
+    }
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use peroxide::prelude::{matrix, Shape::Row};
+
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "Got {}, expected {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn scalar_derivatives_are_exact() {
+        let x = 0.7;
+        let y = HyperDual::new(x, 1.0, 1.0, 0.0);
+
+        let f = y.sin() * y.exp();
+
+        assert_close(f.re, x.sin() * x.exp());
+        assert_close(f.e1, x.exp() * (x.sin() + x.cos()));
+        assert_close(f.e2, f.e1);
+        assert_close(f.e12, 2.0 * x.exp() * x.cos());
+    }
+
+    #[test]
+    fn range_jacobian_and_hessian() {
+        let range = |x: &[HyperDual]| vec![(x[0] * x[0] + x[1] * x[1]).sqrt()];
+        let (px, py): (f64, f64) = (3.0, 4.0);
+        let r: f64 = 5.0;
+        let state = matrix(vec![px, py], 2, 1, Row);
+
+        let j = jacobian(&range, &state);
+        assert_close(j[(0, 0)], px / r);
+        assert_close(j[(0, 1)], py / r);
+
+        let h = &hessians(&range, &state)[0];
+        assert_close(h[(0, 0)], py.powf(2.0) / r.powf(3.0));
+        assert_close(h[(0, 1)], -px * py / r.powf(3.0));
+        assert_close(h[(1, 0)], -px * py / r.powf(3.0));
+        assert_close(h[(1, 1)], px.powf(2.0) / r.powf(3.0));
+    }
+}
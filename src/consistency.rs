@@ -0,0 +1,145 @@
+use peroxide::{fuga::LinearAlgebra, prelude::Matrix};
+
+use crate::stats::chi_square_quantile;
+
+/// Normalised Innovation Squared, `v' S^-1 v`
+pub fn nis(innovation: &Matrix, s: &Matrix) -> f64 {
+    return (&(&innovation.t() * &s.inv()) * innovation)[(0, 0)];
+}
+
+/// Normalised Estimation Error Squared, `e' P^-1 e` with `e = truth - estimate`
+pub fn nees(truth: &Matrix, estimate: &Matrix, p: &Matrix) -> f64 {
+    let e = truth - estimate;
+    return (&(&e.t() * &p.inv()) * &e)[(0, 0)];
+}
+
+/// `None` for an empty slice
+pub fn average(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    return Some(values.iter().sum::<f64>() / values.len() as f64);
+}
+
+/// Two-sided `confidence` region for the average of `samples` independent
+/// chi-square variables with `dof` degrees of freedom. With `samples = 1` this
+/// is the per-step bound.
+pub fn chi_square_bounds(dof: usize, samples: usize, confidence: f64) -> (f64, f64) {
+    let alpha = 1.0 - confidence;
+    let total_dof = dof * samples;
+    let n = samples as f64;
+
+    let lower = chi_square_quantile(0.5 * alpha, total_dof) / n;
+    let upper = chi_square_quantile(1.0 - 0.5 * alpha, total_dof) / n;
+
+    return (lower, upper);
+}
+
+pub struct ConsistencyHistory {
+    pub measurement_dim: usize,
+    pub state_dim: usize,
+    pub nis: Vec<f64>,
+    pub nees: Vec<f64>,
+}
+
+impl ConsistencyHistory {
+    pub fn new(measurement_dim: usize, state_dim: usize) -> ConsistencyHistory {
+        return ConsistencyHistory {
+            measurement_dim,
+            state_dim,
+            nis: vec![],
+            nees: vec![],
+        };
+    }
+
+    pub fn record_nis(&mut self, innovation: &Matrix, s: &Matrix) -> f64 {
+        let value = nis(innovation, s);
+        self.nis.push(value);
+        return value;
+    }
+
+    pub fn record_nees(&mut self, truth: &Matrix, estimate: &Matrix, p: &Matrix) -> f64 {
+        let value = nees(truth, estimate, p);
+        self.nees.push(value);
+        return value;
+    }
+
+    /// `None` until a NIS value has been recorded
+    pub fn average_nis(&self) -> Option<f64> {
+        return average(&self.nis);
+    }
+
+    /// `None` until a NEES value has been recorded
+    pub fn average_nees(&self) -> Option<f64> {
+        return average(&self.nees);
+    }
+
+    pub fn nis_bounds(&self, confidence: f64) -> (f64, f64) {
+        return chi_square_bounds(self.measurement_dim, 1, confidence);
+    }
+
+    pub fn nees_bounds(&self, confidence: f64) -> (f64, f64) {
+        return chi_square_bounds(self.state_dim, 1, confidence);
+    }
+
+    /// `None` until a NIS value has been recorded
+    pub fn average_nis_bounds(&self, confidence: f64) -> Option<(f64, f64)> {
+        if self.nis.is_empty() {
+            return None;
+        }
+        return Some(chi_square_bounds(
+            self.measurement_dim,
+            self.nis.len(),
+            confidence,
+        ));
+    }
+
+    /// `None` until a NEES value has been recorded
+    pub fn average_nees_bounds(&self, confidence: f64) -> Option<(f64, f64)> {
+        if self.nees.is_empty() {
+            return None;
+        }
+        return Some(chi_square_bounds(
+            self.state_dim,
+            self.nees.len(),
+            confidence,
+        ));
+    }
+
+    /// Fraction of per-step NIS values inside the `confidence` bounds; close to
+    /// `confidence` for a consistent filter. `None` until a NIS value has been
+    /// recorded.
+    pub fn nis_fraction_within(&self, confidence: f64) -> Option<f64> {
+        return fraction_within(&self.nis, self.nis_bounds(confidence));
+    }
+
+    /// `None` until a NEES value has been recorded
+    pub fn nees_fraction_within(&self, confidence: f64) -> Option<f64> {
+        return fraction_within(&self.nees, self.nees_bounds(confidence));
+    }
+
+    /// `None` until a NIS value has been recorded
+    pub fn nis_consistent(&self, confidence: f64) -> Option<bool> {
+        let (lower, upper) = self.average_nis_bounds(confidence)?;
+        let avg = self.average_nis()?;
+        return Some(avg >= lower && avg <= upper);
+    }
+
+    /// `None` until a NEES value has been recorded
+    pub fn nees_consistent(&self, confidence: f64) -> Option<bool> {
+        let (lower, upper) = self.average_nees_bounds(confidence)?;
+        let avg = self.average_nees()?;
+        return Some(avg >= lower && avg <= upper);
+    }
+}
+
+fn fraction_within(values: &[f64], bounds: (f64, f64)) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let inside = values
+        .iter()
+        .filter(|v| **v >= bounds.0 && **v <= bounds.1)
+        .count();
+    return Some(inside as f64 / values.len() as f64);
+}
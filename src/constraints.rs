@@ -0,0 +1,197 @@
+use peroxide::{
+    fuga::LinearAlgebra,
+    prelude::{zeros, Matrix},
+};
+
+use crate::{
+    linalg::{block_diagonal, stack_rows},
+    make_k, new_cov,
+    stats::{normal_cdf, normal_pdf},
+};
+
+/// Projects the estimate onto `d x = target` using the covariance as the
+/// weighting, which gives the minimum variance constrained estimate
+pub fn project_equality(x: &Matrix, p: &Matrix, d: &Matrix, target: &Matrix) -> (Matrix, Matrix) {
+    let pd = p * &d.t();
+    let gain = &pd * &(d * &pd).inv();
+
+    let x_projected = x - &(&gain * &(&(d * x) - target));
+    let p_projected = p - &(&gain * &pd.t());
+
+    return (x_projected, p_projected);
+}
+
+/// Applies `d x = target` as a measurement with zero noise
+pub fn perfect_measurement(
+    x: &Matrix,
+    p: &Matrix,
+    d: &Matrix,
+    target: &Matrix,
+) -> (Matrix, Matrix) {
+    let k = make_k(p, d, &zeros(d.row, d.row));
+
+    let x_constrained = x + &(&k * &(target - &(d * x)));
+    let p_constrained = new_cov(&k, d, p);
+
+    return (x_constrained, p_constrained);
+}
+
+/// Appends `d x = target` to a measurement as extra noise-free rows, so the
+/// constraint is enforced by the normal update. Returns the augmented `h`, `r`
+/// and `z`.
+pub fn augment_measurement(
+    h: &Matrix,
+    r: &Matrix,
+    z: &Matrix,
+    d: &Matrix,
+    target: &Matrix,
+) -> (Matrix, Matrix, Matrix) {
+    return (
+        stack_rows(h, d),
+        block_diagonal(r, &zeros(d.row, d.row)),
+        stack_rows(z, target),
+    );
+}
+
+/// Inequality bound on one state component. Use infinite values for a
+/// one-sided bound.
+#[derive(Clone, Copy, Debug)]
+pub struct Bound {
+    pub index: usize,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Bound {
+    pub fn between(index: usize, lower: f64, upper: f64) -> Bound {
+        return Bound {
+            index,
+            lower,
+            upper,
+        };
+    }
+
+    pub fn at_least(index: usize, lower: f64) -> Bound {
+        return Bound::between(index, lower, f64::INFINITY);
+    }
+
+    pub fn at_most(index: usize, upper: f64) -> Bound {
+        return Bound::between(index, f64::NEG_INFINITY, upper);
+    }
+}
+
+/// Projects any component outside its bounds back onto the bound, treating
+/// the violated bounds as active equality constraints. Projection can push
+/// another component out, so this repeats until every bound holds.
+pub fn project_bounds(x: &Matrix, p: &Matrix, bounds: &[Bound]) -> (Matrix, Matrix) {
+    let mut x_projected = x.clone();
+    let mut p_projected = p.clone();
+    let mut active: Vec<(usize, f64)> = vec![];
+
+    for _ in 0..=bounds.len() {
+        let mut changed = false;
+        for bound in bounds {
+            let value = x_projected[(bound.index, 0)];
+            let limit = if value < bound.lower {
+                bound.lower
+            } else if value > bound.upper {
+                bound.upper
+            } else {
+                continue;
+            };
+            if !active.iter().any(|(index, _)| *index == bound.index) {
+                active.push((bound.index, limit));
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let mut d = zeros(active.len(), x.row);
+        let mut target = zeros(active.len(), 1);
+        for (row, (index, limit)) in active.iter().enumerate() {
+            d[(row, *index)] = 1.0;
+            target[(row, 0)] = *limit;
+        }
+
+        // Always project the original estimate with the full active set, as the
+        // projected covariance is singular along earlier constraints
+        (x_projected, p_projected) = project_equality(x, p, &d, &target);
+    }
+
+    return (x_projected, p_projected);
+}
+
+/// Truncates the Gaussian estimate to the bounds and replaces it with the
+/// mean and covariance of the truncated distribution. Bounds are applied one
+/// after the other, each shifting the correlated components through `p`.
+/// States with zero variance are simply clamped to their bounds.
+pub fn truncate_pdf(x: &Matrix, p: &Matrix, bounds: &[Bound]) -> (Matrix, Matrix) {
+    let mut x = x.clone();
+    let mut p = p.clone();
+
+    for bound in bounds {
+        let i = bound.index;
+        let variance = p[(i, i)];
+        // Known exactly, so there is no distribution to truncate
+        if variance <= 0.0 {
+            x[(i, 0)] = x[(i, 0)].max(bound.lower).min(bound.upper);
+            continue;
+        }
+        let sigma = variance.sqrt();
+        let alpha = (bound.lower - x[(i, 0)]) / sigma;
+        let beta = (bound.upper - x[(i, 0)]) / sigma;
+
+        let (mean_shift, truncated_variance) = match truncated_moments(alpha, beta) {
+            Some((mean, var)) => (sigma * mean, variance * var),
+            // Essentially no probability mass inside the bounds
+            None => {
+                let nearest = x[(i, 0)].max(bound.lower).min(bound.upper);
+                (nearest - x[(i, 0)], 0.0)
+            }
+        };
+
+        let column: Vec<f64> = (0..p.row).map(|j| p[(j, i)]).collect();
+        let shrink = (variance - truncated_variance) / variance.powf(2.0);
+        for (j, p_j) in column.iter().enumerate() {
+            x[(j, 0)] += p_j / variance * mean_shift;
+            for (k, p_k) in column.iter().enumerate() {
+                p[(j, k)] -= p_j * p_k * shrink;
+            }
+        }
+    }
+
+    return (x, p);
+}
+
+/// Mean and variance of a standard normal truncated to `[alpha, beta]`
+fn truncated_moments(alpha: f64, beta: f64) -> Option<(f64, f64)> {
+    // Work in the lower tail to avoid cancellation between two CDFs near 1
+    let mass = if alpha > 0.0 {
+        normal_cdf(-alpha) - normal_cdf(-beta)
+    } else {
+        normal_cdf(beta) - normal_cdf(alpha)
+    };
+    if mass < 1e-12 {
+        return None;
+    }
+
+    let pdf_alpha = normal_pdf(alpha);
+    let pdf_beta = normal_pdf(beta);
+    let alpha_term = if alpha.is_finite() {
+        alpha * pdf_alpha
+    } else {
+        0.0
+    };
+    let beta_term = if beta.is_finite() {
+        beta * pdf_beta
+    } else {
+        0.0
+    };
+
+    let mean = (pdf_alpha - pdf_beta) / mass;
+    let variance = 1.0 + (alpha_term - beta_term) / mass - mean.powf(2.0);
+
+    return Some((mean, variance));
+}
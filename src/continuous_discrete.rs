@@ -0,0 +1,110 @@
+use peroxide::prelude::Matrix;
+
+use crate::{
+    filter::Correction,
+    integrators::{IntegrationError, IntegrationStats, Rk45},
+    linalg::{pack_state, symmetrise, unpack_state},
+    make_k, make_s, new_cov,
+};
+
+/// Extended Kalman filter for continuous dynamics `x_dot = f(t, x) + w` with
+/// discrete measurements. Between measurements the state and the covariance
+/// `P_dot = F P + P F' + Q` are integrated together, with `F` the Jacobian of
+/// `f` and `Q` the spectral density of `w`.
+pub struct ContinuousDiscreteEkf {
+    pub x: Matrix,
+    pub p: Matrix,
+    pub t: f64,
+    pub q: Matrix,
+    pub integrator: Rk45,
+    /// Step statistics of the most recent prediction
+    pub stats: IntegrationStats,
+    f: Box<dyn Fn(f64, &Matrix) -> Matrix>,
+    jacobian: Box<dyn Fn(f64, &Matrix) -> Matrix>,
+}
+
+impl ContinuousDiscreteEkf {
+    pub fn new<F, J>(
+        x: Matrix,
+        p: Matrix,
+        t: f64,
+        f: F,
+        jacobian: J,
+        q: Matrix,
+    ) -> ContinuousDiscreteEkf
+    where
+        F: Fn(f64, &Matrix) -> Matrix + 'static,
+        J: Fn(f64, &Matrix) -> Matrix + 'static,
+    {
+        return ContinuousDiscreteEkf {
+            x,
+            p,
+            t,
+            q,
+            integrator: Rk45::default(),
+            stats: IntegrationStats::default(),
+            f: Box::new(f),
+            jacobian: Box::new(jacobian),
+        };
+    }
+
+    pub fn with_integrator(mut self, integrator: Rk45) -> ContinuousDiscreteEkf {
+        self.integrator = integrator;
+        return self;
+    }
+
+    /// Leaves the filter unchanged if the integration fails
+    pub fn predict(&mut self, t: f64) -> Result<(), IntegrationError> {
+        assert!(
+            t >= self.t,
+            "Cannot predict backwards from {} to {}",
+            self.t,
+            t
+        );
+        if t == self.t {
+            return Ok(());
+        }
+
+        let n = self.x.row;
+        let derivative = |time: f64, packed: &Matrix| {
+            let (x, p) = unpack_state(packed, n);
+            let f = (self.jacobian)(time, &x);
+            let x_dot = (self.f)(time, &x);
+            let p_dot = &(&(&f * &p) + &(&p * &f.t())) + &self.q;
+            return pack_state(&x_dot, &p_dot);
+        };
+
+        let (packed, stats) =
+            self.integrator
+                .integrate(derivative, self.t, &pack_state(&self.x, &self.p), t)?;
+        let (x, p) = unpack_state(&packed, n);
+
+        self.x = x;
+        self.p = symmetrise(&p);
+        self.t = t;
+        self.stats = stats;
+        return Ok(());
+    }
+
+    /// Discrete update with measurement function `h` and its Jacobian
+    pub fn update<H, J>(&mut self, z: &Matrix, h: H, jacobian: J, r: &Matrix) -> Correction
+    where
+        H: Fn(&Matrix) -> Matrix,
+        J: Fn(&Matrix) -> Matrix,
+    {
+        let innovation = z - &h(&self.x);
+        let h_matrix = jacobian(&self.x);
+
+        let s = make_s(&self.p, &h_matrix, r);
+        let k = make_k(&self.p, &h_matrix, r);
+
+        self.x = &self.x + &(&k * &innovation);
+        self.p = new_cov(&k, &h_matrix, &self.p);
+
+        return Correction {
+            innovation,
+            innovation_cov: s,
+            gain: k,
+        };
+    }
+}
@@ -0,0 +1,125 @@
+use peroxide::prelude::{eye, Matrix};
+
+use crate::{make_k, make_m, models::MotionModel, new_cov, simulation::even_times};
+
+/// Covariances and gain at one step of a covariance analysis
+#[derive(Clone, Debug)]
+pub struct CovarianceStep {
+    pub t: f64,
+    /// Covariance the filter believes it has before and after the update
+    pub prior_cov: Matrix,
+    pub posterior_cov: Matrix,
+    pub gain: Matrix,
+    /// Actual error covariance after the update when the true `Q` and `R`
+    /// differ from the filter's; equal to `posterior_cov` otherwise
+    pub true_cov: Matrix,
+}
+
+impl CovarianceStep {
+    /// Standard deviation the filter predicts for one state
+    pub fn std(&self, state: usize) -> f64 {
+        return self.posterior_cov[(state, state)].sqrt();
+    }
+
+    /// Standard deviation of the actual error in one state
+    pub fn true_std(&self, state: usize) -> f64 {
+        return self.true_cov[(state, state)].sqrt();
+    }
+}
+
+/// Runs the Riccati recursion alone to predict a filter's accuracy before any
+/// data exists. The filter's gains come from its assumed `q` and `r`; if the
+/// true noise is set with `with_true_noise` the actual error covariance is
+/// propagated alongside with those gains, using the Joseph form since the
+/// gains are no longer optimal.
+pub struct CovarianceAnalysis {
+    pub h: Matrix,
+    pub r: Matrix,
+    pub true_r: Option<Matrix>,
+    phi: Box<dyn Fn(f64) -> Matrix>,
+    q: Box<dyn Fn(f64) -> Matrix>,
+    true_q: Option<Box<dyn Fn(f64) -> Matrix>>,
+}
+
+impl CovarianceAnalysis {
+    pub fn new<F, Q>(phi: F, q: Q, h: Matrix, r: Matrix) -> CovarianceAnalysis
+    where
+        F: Fn(f64) -> Matrix + 'static,
+        Q: Fn(f64) -> Matrix + 'static,
+    {
+        return CovarianceAnalysis {
+            h,
+            r,
+            true_r: None,
+            phi: Box::new(phi),
+            q: Box::new(q),
+            true_q: None,
+        };
+    }
+
+    pub fn from_model<M>(model: M, h: Matrix, r: Matrix) -> CovarianceAnalysis
+    where
+        M: MotionModel + Clone + 'static,
+    {
+        let q_model = model.clone();
+        return CovarianceAnalysis::new(move |dt| model.phi(dt), move |dt| q_model.q(dt), h, r);
+    }
+
+    /// Sets the process and measurement noise the filter will really see
+    pub fn with_true_noise<Q>(mut self, q: Q, r: Matrix) -> CovarianceAnalysis
+    where
+        Q: Fn(f64) -> Matrix + 'static,
+    {
+        self.true_q = Some(Box::new(q));
+        self.true_r = Some(r);
+        return self;
+    }
+
+    /// Starts from `p0` at `times[0]` and updates at every one of `times`
+    pub fn run(&self, p0: &Matrix, times: &[f64]) -> Vec<CovarianceStep> {
+        let mut steps = vec![];
+        let mut p = p0.clone();
+        let mut true_p = p0.clone();
+        let mut t_previous = times.first().copied().unwrap_or(0.0);
+
+        for t in times {
+            let dt = t - t_previous;
+            let phi = (self.phi)(dt);
+            let q = (self.q)(dt);
+
+            let m = make_m(&phi, &p, &q);
+            let k = make_k(&m, &self.h, &self.r);
+            p = new_cov(&k, &self.h, &m);
+
+            true_p = match (&self.true_q, &self.true_r) {
+                (Some(true_q), Some(true_r)) => {
+                    let true_m = make_m(&phi, &true_p, &true_q(dt));
+                    joseph(&k, &self.h, &true_m, true_r)
+                }
+                _ => p.clone(),
+            };
+
+            steps.push(CovarianceStep {
+                t: *t,
+                prior_cov: m,
+                posterior_cov: p.clone(),
+                gain: k,
+                true_cov: true_p.clone(),
+            });
+            t_previous = *t;
+        }
+
+        return steps;
+    }
+
+    /// `run` over evenly spaced times from `t0` while `t < t_end`
+    pub fn run_for(&self, p0: &Matrix, t0: f64, t_end: f64, dt: f64) -> Vec<CovarianceStep> {
+        return self.run(p0, &even_times(t0, t_end, dt));
+    }
+}
+
+/// Joseph form `(I - K H) M (I - K H)' + K R K'`, valid for any gain
+pub fn joseph(k: &Matrix, h: &Matrix, m: &Matrix, r: &Matrix) -> Matrix {
+    let i_kh = &eye(m.row) - &(k * h);
+    return &(&(&i_kh * m) * &i_kh.t()) + &(&(k * r) * &k.t());
+}
@@ -0,0 +1,223 @@
+use peroxide::{
+    fuga::LinearAlgebra,
+    prelude::{eye, zeros, Matrix},
+};
+
+use crate::models::MotionModel;
+
+const PADE_ORDER: usize = 6;
+
+/// Matrix exponential by scaling and squaring with a diagonal Padé
+/// approximant
+pub fn expm(a: &Matrix) -> Matrix {
+    let n = a.row;
+
+    let norm = (0..n)
+        .map(|i| (0..n).map(|j| a[(i, j)].abs()).sum::<f64>())
+        .fold(0.0, f64::max);
+    let squarings = if norm > 0.5 {
+        (norm / 0.5).log2().ceil() as i32
+    } else {
+        0
+    };
+    let scaled = a.clone() * (1.0 / 2.0f64.powi(squarings));
+
+    let mut numerator = eye(n);
+    let mut denominator = eye(n);
+    let mut power = eye(n);
+    let mut c = 1.0;
+    for k in 1..=PADE_ORDER {
+        c *= (PADE_ORDER - k + 1) as f64 / (k * (2 * PADE_ORDER - k + 1)) as f64;
+        power = &power * &scaled;
+        let term = power.clone() * c;
+        numerator = &numerator + &term;
+        if k % 2 == 0 {
+            denominator = &denominator + &term;
+        } else {
+            denominator = &denominator - &term;
+        }
+    }
+
+    let mut result = &denominator.inv() * &numerator;
+    for _ in 0..squarings {
+        result = &result * &result;
+    }
+
+    return result;
+}
+
+/// Van Loan's method: exact discrete `phi` and `Q` over `dt` for the
+/// continuous system `x_dot = F x + G w`, where `w` is white noise with
+/// spectral density `qc`
+pub fn van_loan(f: &Matrix, g: &Matrix, qc: &Matrix, dt: f64) -> (Matrix, Matrix) {
+    let n = f.row;
+    let gqg = &(g * qc) * &g.t();
+
+    let mut a = zeros(2 * n, 2 * n);
+    for i in 0..n {
+        for j in 0..n {
+            a[(i, j)] = -f[(i, j)] * dt;
+            a[(i, n + j)] = gqg[(i, j)] * dt;
+            a[(n + i, n + j)] = f[(j, i)] * dt;
+        }
+    }
+    let b = expm(&a);
+
+    let mut b12 = zeros(n, n);
+    let mut phi = zeros(n, n);
+    for i in 0..n {
+        for j in 0..n {
+            b12[(i, j)] = b[(i, n + j)];
+            phi[(i, j)] = b[(n + j, n + i)];
+        }
+    }
+    let q = &phi * &b12;
+
+    return (phi, q);
+}
+
+/// Linear time-invariant continuous system, discretised exactly for any `dt`
+#[derive(Clone, Debug)]
+pub struct LinearSystem {
+    pub f: Matrix,
+    pub g: Matrix,
+    pub qc: Matrix,
+}
+
+impl LinearSystem {
+    pub fn new(f: Matrix, g: Matrix, qc: Matrix) -> LinearSystem {
+        return LinearSystem { f, g, qc };
+    }
+
+    pub fn discretise(&self, dt: f64) -> (Matrix, Matrix) {
+        return van_loan(&self.f, &self.g, &self.qc, dt);
+    }
+}
+
+impl MotionModel for LinearSystem {
+    fn state_dim(&self) -> usize {
+        return self.f.row;
+    }
+
+    fn phi(&self, dt: f64) -> Matrix {
+        return expm(&(self.f.clone() * dt));
+    }
+
+    fn q(&self, dt: f64) -> Matrix {
+        return self.discretise(dt).1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use peroxide::prelude::{matrix, Shape::Row};
+
+    use super::*;
+
+    fn assert_close(actual: &Matrix, expected: &Matrix, tolerance: f64) {
+        for i in 0..expected.row {
+            for j in 0..expected.col {
+                assert!(
+                    (actual[(i, j)] - expected[(i, j)]).abs() < tolerance,
+                    "Element ({}, {}) was {}, expected {}",
+                    i,
+                    j,
+                    actual[(i, j)],
+                    expected[(i, j)]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn expm_of_rotation_generator_is_rotation() {
+        let theta = 2.5;
+        let a = matrix(vec![0.0, -theta, theta, 0.0], 2, 2, Row);
+        let expected = matrix(
+            vec![theta.cos(), -theta.sin(), theta.sin(), theta.cos()],
+            2,
+            2,
+            Row,
+        );
+        assert_close(&expm(&a), &expected, 1e-10);
+    }
+
+    #[test]
+    fn van_loan_matches_constant_velocity() {
+        let (phis, dt) = (0.3, 0.7);
+        let f = matrix(vec![0.0, 1.0, 0.0, 0.0], 2, 2, Row);
+        let g = matrix(vec![0.0, 1.0], 2, 1, Row);
+        let qc = matrix(vec![phis], 1, 1, Row);
+
+        let (phi, q) = van_loan(&f, &g, &qc, dt);
+
+        let expected_phi = matrix(vec![1.0, dt, 0.0, 1.0], 2, 2, Row);
+        let expected_q = matrix(
+            vec![
+                dt.powf(3.0) / 3.0,
+                dt.powf(2.0) / 2.0,
+                dt.powf(2.0) / 2.0,
+                dt,
+            ],
+            2,
+            2,
+            Row,
+        ) * phis;
+        assert_close(&phi, &expected_phi, 1e-10);
+        assert_close(&q, &expected_q, 1e-10);
+    }
+
+    #[test]
+    fn van_loan_matches_constant_acceleration() {
+        let (phis, dt) = (2.0, 1.5);
+        let f = matrix(
+            vec![
+                0.0, 1.0, 0.0, //
+                0.0, 0.0, 1.0, //
+                0.0, 0.0, 0.0, //
+            ],
+            3,
+            3,
+            Row,
+        );
+        let g = matrix(vec![0.0, 0.0, 1.0], 3, 1, Row);
+        let qc = matrix(vec![phis], 1, 1, Row);
+
+        let (phi, q) = van_loan(&f, &g, &qc, dt);
+
+        let expected_phi = matrix(
+            vec![
+                1.0,
+                dt,
+                dt.powf(2.0) / 2.0, //
+                0.0,
+                1.0,
+                dt, //
+                0.0,
+                0.0,
+                1.0, //
+            ],
+            3,
+            3,
+            Row,
+        );
+        let expected_q = matrix(
+            vec![
+                dt.powf(5.0) / 20.0,
+                dt.powf(4.0) / 8.0,
+                dt.powf(3.0) / 6.0, //
+                dt.powf(4.0) / 8.0,
+                dt.powf(3.0) / 3.0,
+                dt.powf(2.0) / 2.0, //
+                dt.powf(3.0) / 6.0,
+                dt.powf(2.0) / 2.0,
+                dt, //
+            ],
+            3,
+            3,
+            Row,
+        ) * phis;
+        assert_close(&phi, &expected_phi, 1e-9);
+        assert_close(&q, &expected_q, 1e-9);
+    }
+}
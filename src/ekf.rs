@@ -0,0 +1,130 @@
+use peroxide::{
+    fuga::LinearAlgebra,
+    prelude::{zeros, Matrix},
+};
+
+use crate::{linalg::trace, make_k, make_s, new_cov, Convergence};
+
+pub struct EkfUpdate {
+    pub x_hat: Matrix,
+    pub p: Matrix,
+    /// Innovation and its covariance from the final linearisation
+    pub innovation: Matrix,
+    pub innovation_cov: Matrix,
+    pub gain: Matrix,
+    /// Number of linearisations of the measurement model
+    pub iterations: usize,
+}
+
+/// Extended Kalman filter update, linearising the measurement model `h` once
+/// about the prediction `x_bar`
+pub fn ekf_update<H, J>(
+    x_bar: &Matrix,
+    m: &Matrix,
+    z: &Matrix,
+    h: H,
+    jacobian: J,
+    r: &Matrix,
+) -> EkfUpdate
+where
+    H: Fn(&Matrix) -> Matrix,
+    J: Fn(&Matrix) -> Matrix,
+{
+    let convergence = Convergence {
+        max_iterations: 1,
+        ..Convergence::default()
+    };
+    return iterated_ekf_update(x_bar, m, z, h, jacobian, r, &convergence);
+}
+
+/// Iterated extended Kalman filter update. The measurement model is
+/// relinearised about each new estimate, a Gauss-Newton search for the
+/// maximum a posteriori state, until the estimate moves by less than the
+/// tolerance in every component.
+pub fn iterated_ekf_update<H, J>(
+    x_bar: &Matrix,
+    m: &Matrix,
+    z: &Matrix,
+    h: H,
+    jacobian: J,
+    r: &Matrix,
+    convergence: &Convergence,
+) -> EkfUpdate
+where
+    H: Fn(&Matrix) -> Matrix,
+    J: Fn(&Matrix) -> Matrix,
+{
+    let mut x_i = x_bar.clone();
+    let mut iterations = 0;
+
+    loop {
+        iterations += 1;
+
+        let h_matrix = jacobian(&x_i);
+        let innovation = &(z - &h(&x_i)) - &(&h_matrix * &(x_bar - &x_i));
+        let k = make_k(m, &h_matrix, r);
+        let x_next = x_bar + &(&k * &innovation);
+
+        let change = (&x_next - &x_i)
+            .data
+            .iter()
+            .fold(0.0, |max, value| value.abs().max(max));
+        x_i = x_next;
+
+        if change < convergence.tolerance || iterations >= convergence.max_iterations {
+            return EkfUpdate {
+                x_hat: x_i,
+                p: new_cov(&k, &h_matrix, m),
+                innovation,
+                innovation_cov: make_s(m, &h_matrix, r),
+                gain: k,
+                iterations,
+            };
+        }
+    }
+}
+
+/// Second-order extended Kalman filter update. Adds the bias `tr(H_i M) / 2`
+/// to each predicted measurement and `tr(H_i M H_j M) / 2` to the innovation
+/// covariance, where `H_i` is the Hessian of the i-th measurement component
+/// from `hessians`. See `autodiff::hessians` for Hessians of models written
+/// over `HyperDual`.
+pub fn second_order_ekf_update<H, J, D>(
+    x_bar: &Matrix,
+    m: &Matrix,
+    z: &Matrix,
+    h: H,
+    jacobian: J,
+    hessians: D,
+    r: &Matrix,
+) -> EkfUpdate
+where
+    H: Fn(&Matrix) -> Matrix,
+    J: Fn(&Matrix) -> Matrix,
+    D: Fn(&Matrix) -> Vec<Matrix>,
+{
+    let h_matrix = jacobian(x_bar);
+    let curvature: Vec<Matrix> = hessians(x_bar).iter().map(|d| d * m).collect();
+
+    let mut z_bar = h(x_bar);
+    let mut bias_cov = zeros(z.row, z.row);
+    for (i, di_m) in curvature.iter().enumerate() {
+        z_bar[(i, 0)] += 0.5 * trace(di_m);
+        for (j, dj_m) in curvature.iter().enumerate() {
+            bias_cov[(i, j)] = 0.5 * trace(&(di_m * dj_m));
+        }
+    }
+
+    let innovation = z - &z_bar;
+    let s = &make_s(m, &h_matrix, r) + &bias_cov;
+    let k = &(m * &h_matrix.t()) * &s.inv();
+
+    return EkfUpdate {
+        x_hat: x_bar + &(&k * &innovation),
+        p: new_cov(&k, &h_matrix, m),
+        innovation,
+        innovation_cov: s,
+        gain: k,
+        iterations: 1,
+    };
+}
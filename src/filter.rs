@@ -0,0 +1,233 @@
+use peroxide::prelude::Matrix;
+
+use crate::{
+    make_k, make_m, make_s,
+    models::MotionModel,
+    new_cov,
+    sequential::{sequential_gain, sequential_update},
+};
+
+#[derive(Clone, Debug)]
+pub struct StepRecord {
+    pub t: f64,
+    pub prior: Matrix,
+    pub prior_cov: Matrix,
+    pub posterior: Matrix,
+    pub posterior_cov: Matrix,
+    /// `None` when no measurement was available at this step
+    pub innovation: Option<Matrix>,
+    pub innovation_cov: Option<Matrix>,
+    pub gain: Option<Matrix>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Correction {
+    pub innovation: Matrix,
+    pub innovation_cov: Matrix,
+    pub gain: Matrix,
+}
+
+/// Kalman filter driven by measurement timestamps rather than a fixed `TS`.
+/// `phi` and `q` are evaluated for whatever `dt` separates two steps.
+pub struct KalmanFilter {
+    pub x: Matrix,
+    pub p: Matrix,
+    pub t: f64,
+    pub h: Matrix,
+    pub r: Matrix,
+    phi: Box<dyn Fn(f64) -> Matrix>,
+    q: Box<dyn Fn(f64) -> Matrix>,
+    propagate: Option<Box<dyn Fn(&Matrix, f64) -> Matrix>>,
+}
+
+impl KalmanFilter {
+    pub fn new<F, Q>(
+        x: Matrix,
+        p: Matrix,
+        t: f64,
+        phi: F,
+        q: Q,
+        h: Matrix,
+        r: Matrix,
+    ) -> KalmanFilter
+    where
+        F: Fn(f64) -> Matrix + 'static,
+        Q: Fn(f64) -> Matrix + 'static,
+    {
+        return KalmanFilter {
+            x,
+            p,
+            t,
+            h,
+            r,
+            phi: Box::new(phi),
+            q: Box::new(q),
+            propagate: None,
+        };
+    }
+
+    pub fn from_model<M>(
+        x: Matrix,
+        p: Matrix,
+        t: f64,
+        model: M,
+        h: Matrix,
+        r: Matrix,
+    ) -> KalmanFilter
+    where
+        M: MotionModel + Clone + 'static,
+    {
+        let q_model = model.clone();
+        return KalmanFilter::new(
+            x,
+            p,
+            t,
+            move |dt| model.phi(dt),
+            move |dt| q_model.q(dt),
+            h,
+            r,
+        );
+    }
+
+    /// Replaces `phi(dt) * x` for the state prediction, for example to add a
+    /// known control input such as gravity. The covariance still uses `phi`.
+    pub fn with_propagation<P>(mut self, propagate: P) -> KalmanFilter
+    where
+        P: Fn(&Matrix, f64) -> Matrix + 'static,
+    {
+        self.propagate = Some(Box::new(propagate));
+        return self;
+    }
+
+    pub fn phi(&self, dt: f64) -> Matrix {
+        return (self.phi)(dt);
+    }
+
+    pub fn q(&self, dt: f64) -> Matrix {
+        return (self.q)(dt);
+    }
+
+    /// True when the state is propagated with `phi` alone
+    pub fn is_linear(&self) -> bool {
+        return self.propagate.is_none();
+    }
+
+    pub fn propagate(&self, x: &Matrix, dt: f64) -> Matrix {
+        return match &self.propagate {
+            Some(propagate) => propagate(x, dt),
+            None => &self.phi(dt) * x,
+        };
+    }
+
+    pub fn predict(&mut self, t: f64) {
+        let dt = t - self.t;
+        assert!(
+            dt >= 0.0,
+            "Cannot predict backwards from {} to {}",
+            self.t,
+            t
+        );
+
+        if dt > 0.0 {
+            self.x = self.propagate(&self.x, dt);
+            self.p = make_m(&self.phi(dt), &self.p, &self.q(dt));
+        }
+        self.t = t;
+    }
+
+    pub fn update(&mut self, z: &Matrix) -> Correction {
+        let innovation = z - &(&self.h * &self.x);
+        let h = self.h.clone();
+        let r = self.r.clone();
+        return self.correct(&innovation, &h, &r);
+    }
+
+    /// Corrects the current estimate with an already formed innovation. For a
+    /// non-linear measurement pass `z - h(x)` and the measurement Jacobian.
+    pub fn correct(&mut self, innovation: &Matrix, h: &Matrix, r: &Matrix) -> Correction {
+        let s = make_s(&self.p, h, r);
+        let k = make_k(&self.p, h, r);
+
+        self.x = &self.x + &(&k * innovation);
+        self.p = new_cov(&k, h, &self.p);
+
+        return Correction {
+            innovation: innovation.clone(),
+            innovation_cov: s,
+            gain: k,
+        };
+    }
+
+    /// Same as `update` but processes `z` one component at a time, avoiding
+    /// the inversion of the innovation covariance
+    pub fn update_sequential(&mut self, z: &Matrix) -> Correction {
+        let innovation = z - &(&self.h * &self.x);
+        let innovation_cov = make_s(&self.p, &self.h, &self.r);
+        let (x, p) = sequential_update(&self.x, &self.p, &self.h, &self.r, &innovation);
+        let gain = sequential_gain(&p, &self.h, &self.r);
+        self.x = x;
+        self.p = p;
+
+        return Correction {
+            innovation,
+            innovation_cov,
+            gain,
+        };
+    }
+
+    /// Predicts to `t` and applies `z` if there is one
+    pub fn step(&mut self, t: f64, z: Option<&Matrix>) -> StepRecord {
+        self.predict(t);
+        let prior = self.x.clone();
+        let prior_cov = self.p.clone();
+
+        let correction = z.map(|z| self.update(z));
+
+        return self.record(prior, prior_cov, correction);
+    }
+
+    /// Predict-only segment for a measurement outage, recording a step every
+    /// `dt` up to and including `t_end`
+    pub fn coast(&mut self, t_end: f64, dt: f64) -> Vec<StepRecord> {
+        assert!(dt > 0.0, "Coasting step must be positive");
+        let mut records = vec![];
+        while t_end - self.t > dt {
+            let t = self.t + dt;
+            records.push(self.step(t, None));
+        }
+        if t_end > self.t {
+            records.push(self.step(t_end, None));
+        }
+        return records;
+    }
+
+    pub fn run(&mut self, measurements: &[(f64, Option<Matrix>)]) -> Vec<StepRecord> {
+        return measurements
+            .iter()
+            .map(|(t, z)| self.step(*t, z.as_ref()))
+            .collect();
+    }
+
+    pub fn record(
+        &self,
+        prior: Matrix,
+        prior_cov: Matrix,
+        correction: Option<Correction>,
+    ) -> StepRecord {
+        let (innovation, innovation_cov, gain) = match correction {
+            Some(c) => (Some(c.innovation), Some(c.innovation_cov), Some(c.gain)),
+            None => (None, None, None),
+        };
+
+        return StepRecord {
+            t: self.t,
+            prior,
+            prior_cov,
+            posterior: self.x.clone(),
+            posterior_cov: self.p.clone(),
+            innovation,
+            innovation_cov,
+            gain,
+        };
+    }
+}
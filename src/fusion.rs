@@ -0,0 +1,172 @@
+use peroxide::prelude::Matrix;
+
+use crate::{
+    consistency::{average, nis},
+    filter::{KalmanFilter, StepRecord},
+};
+
+pub struct Sensor {
+    pub name: String,
+    pub r: Matrix,
+    /// Nominal update rate in Hz
+    pub rate: f64,
+    h: Box<dyn Fn(&Matrix) -> Matrix>,
+    jacobian: Box<dyn Fn(&Matrix) -> Matrix>,
+}
+
+impl Sensor {
+    pub fn linear(name: &str, h: Matrix, r: Matrix, rate: f64) -> Sensor {
+        let jacobian = h.clone();
+        return Sensor {
+            name: name.to_string(),
+            r,
+            rate,
+            h: Box::new(move |x| &h * x),
+            jacobian: Box::new(move |_| jacobian.clone()),
+        };
+    }
+
+    /// `h` maps a state to the expected measurement and `jacobian` gives its
+    /// linearisation at that state
+    pub fn non_linear<H, J>(name: &str, h: H, jacobian: J, r: Matrix, rate: f64) -> Sensor
+    where
+        H: Fn(&Matrix) -> Matrix + 'static,
+        J: Fn(&Matrix) -> Matrix + 'static,
+    {
+        return Sensor {
+            name: name.to_string(),
+            r,
+            rate,
+            h: Box::new(h),
+            jacobian: Box::new(jacobian),
+        };
+    }
+
+    pub fn period(&self) -> f64 {
+        return 1.0 / self.rate;
+    }
+
+    pub fn predict_measurement(&self, x: &Matrix) -> Matrix {
+        return (self.h)(x);
+    }
+
+    pub fn jacobian(&self, x: &Matrix) -> Matrix {
+        return (self.jacobian)(x);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SensorMeasurement {
+    pub sensor: usize,
+    pub t: f64,
+    pub z: Matrix,
+    /// Replaces the sensor's `r` for this measurement only, for sensors that
+    /// report their own accuracy
+    pub r: Option<Matrix>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SensorDiagnostics {
+    pub t: Vec<f64>,
+    pub innovations: Vec<Matrix>,
+    pub nis: Vec<f64>,
+    /// Measurements older than the filter time, which were skipped. Use
+    /// `oosm::OosmFilter` if these need to be incorporated.
+    pub late: usize,
+}
+
+impl SensorDiagnostics {
+    pub fn updates(&self) -> usize {
+        return self.t.len();
+    }
+
+    /// `None` until the sensor has made an update
+    pub fn average_nis(&self) -> Option<f64> {
+        return average(&self.nis);
+    }
+
+    /// Rate the sensor actually delivered, to compare against `Sensor::rate`.
+    /// `None` until updates at two different times have been made.
+    pub fn observed_rate(&self) -> Option<f64> {
+        if self.t.len() < 2 {
+            return None;
+        }
+        let span = self.t[self.t.len() - 1] - self.t[0];
+        if span <= 0.0 {
+            return None;
+        }
+        return Some((self.t.len() - 1) as f64 / span);
+    }
+}
+
+/// Applies measurements from any number of sensors to one shared filter
+pub struct SensorFusion {
+    pub filter: KalmanFilter,
+    pub sensors: Vec<Sensor>,
+    pub diagnostics: Vec<SensorDiagnostics>,
+}
+
+impl SensorFusion {
+    pub fn new(filter: KalmanFilter) -> SensorFusion {
+        return SensorFusion {
+            filter,
+            sensors: vec![],
+            diagnostics: vec![],
+        };
+    }
+
+    /// Registers a sensor and returns the id to use in `SensorMeasurement`
+    pub fn add_sensor(&mut self, sensor: Sensor) -> usize {
+        self.sensors.push(sensor);
+        self.diagnostics.push(SensorDiagnostics::default());
+        return self.sensors.len() - 1;
+    }
+
+    /// Predicts to the measurement time and applies it with the sensor's own
+    /// model. Returns `None` for a measurement older than the filter.
+    pub fn apply(&mut self, measurement: &SensorMeasurement) -> Option<StepRecord> {
+        let sensor = &self.sensors[measurement.sensor];
+        let diagnostics = &mut self.diagnostics[measurement.sensor];
+
+        if measurement.t < self.filter.t {
+            diagnostics.late += 1;
+            return None;
+        }
+
+        self.filter.predict(measurement.t);
+        let prior = self.filter.x.clone();
+        let prior_cov = self.filter.p.clone();
+
+        let innovation = &measurement.z - &sensor.predict_measurement(&prior);
+        let h = sensor.jacobian(&prior);
+        let r = measurement.r.as_ref().unwrap_or(&sensor.r);
+        let correction = self.filter.correct(&innovation, &h, r);
+
+        diagnostics.t.push(measurement.t);
+        diagnostics
+            .nis
+            .push(nis(&correction.innovation, &correction.innovation_cov));
+        diagnostics.innovations.push(innovation);
+
+        return Some(self.filter.record(prior, prior_cov, Some(correction)));
+    }
+
+    /// Sorts the measurements by time and applies them in order
+    pub fn process(&mut self, mut measurements: Vec<SensorMeasurement>) -> Vec<StepRecord> {
+        measurements.sort_by(|a, b| a.t.partial_cmp(&b.t).expect("Measurement time is NaN"));
+        return measurements
+            .iter()
+            .filter_map(|measurement| self.apply(measurement))
+            .collect();
+    }
+
+    /// Id of the sensor registered under `name`
+    pub fn sensor_id(&self, name: &str) -> Option<usize> {
+        return self.sensors.iter().position(|s| s.name == name);
+    }
+
+    pub fn diagnostics_for(&self, name: &str) -> Option<&SensorDiagnostics> {
+        let index = self.sensor_id(name)?;
+        return Some(&self.diagnostics[index]);
+    }
+}
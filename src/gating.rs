@@ -0,0 +1,156 @@
+use peroxide::prelude::Matrix;
+
+use crate::{consistency::nis, make_s, stats::chi_square_quantile};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GatePolicy {
+    /// Drop the measurement entirely
+    Reject,
+    /// Scale `r` up until the innovation sits on the gate boundary
+    InflateR,
+    /// Shrink the innovation onto the gate boundary
+    Clamp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GateDecision {
+    Accepted,
+    Rejected,
+    Inflated,
+    Clamped,
+}
+
+#[derive(Clone, Debug)]
+pub struct GateRecord {
+    pub step: usize,
+    pub distance: f64,
+    pub decision: GateDecision,
+}
+
+/// What the caller should feed into `make_k` and the state correction after
+/// gating. `None` from `Gate::check` means the measurement was rejected.
+pub struct Gated {
+    pub innovation: Matrix,
+    pub r: Matrix,
+}
+
+pub struct Gate {
+    pub threshold: f64,
+    pub policy: GatePolicy,
+    pub history: Vec<GateRecord>,
+}
+
+impl Gate {
+    pub fn new(threshold: f64, policy: GatePolicy) -> Gate {
+        return Gate {
+            threshold,
+            policy,
+            history: vec![],
+        };
+    }
+
+    /// Gate at the chi-square quantile for `probability` with one degree of
+    /// freedom per measurement component
+    pub fn from_probability(measurement_dim: usize, probability: f64, policy: GatePolicy) -> Gate {
+        return Gate::new(chi_square_quantile(probability, measurement_dim), policy);
+    }
+
+    /// Checks an innovation `z - h(x_bar)` against the gate. For an extended
+    /// filter pass the measurement Jacobian as `h`.
+    pub fn check(
+        &mut self,
+        innovation: &Matrix,
+        m: &Matrix,
+        h: &Matrix,
+        r: &Matrix,
+    ) -> Option<Gated> {
+        let s = make_s(m, h, r);
+        let distance = nis(innovation, &s);
+        let step = self.history.len();
+
+        if distance <= self.threshold {
+            self.history.push(GateRecord {
+                step,
+                distance,
+                decision: GateDecision::Accepted,
+            });
+            return Some(Gated {
+                innovation: innovation.clone(),
+                r: r.clone(),
+            });
+        }
+
+        let (decision, gated) = match self.policy {
+            GatePolicy::Reject => (GateDecision::Rejected, None),
+            GatePolicy::InflateR => {
+                let r_inflated = inflate_r(innovation, m, h, r, self.threshold);
+                (
+                    GateDecision::Inflated,
+                    Some(Gated {
+                        innovation: innovation.clone(),
+                        r: r_inflated,
+                    }),
+                )
+            }
+            GatePolicy::Clamp => {
+                let scale = (self.threshold / distance).sqrt();
+                (
+                    GateDecision::Clamped,
+                    Some(Gated {
+                        innovation: innovation.clone() * scale,
+                        r: r.clone(),
+                    }),
+                )
+            }
+        };
+
+        self.history.push(GateRecord {
+            step,
+            distance,
+            decision,
+        });
+
+        return gated;
+    }
+
+    pub fn gated_steps(&self) -> Vec<usize> {
+        return self
+            .history
+            .iter()
+            .filter(|record| record.decision != GateDecision::Accepted)
+            .map(|record| record.step)
+            .collect();
+    }
+
+    pub fn gated_fraction(&self) -> f64 {
+        if self.history.is_empty() {
+            return 0.0;
+        }
+        return self.gated_steps().len() as f64 / self.history.len() as f64;
+    }
+}
+
+/// Finds the scale `a` such that the innovation has Mahalanobis distance
+/// `threshold` under `h m h' + a r`. The distance is monotone in `a`, so
+/// bisection is enough.
+fn inflate_r(innovation: &Matrix, m: &Matrix, h: &Matrix, r: &Matrix, threshold: f64) -> Matrix {
+    let distance = |a: f64| nis(innovation, &make_s(m, h, &(r.clone() * a)));
+
+    let mut low = 1.0;
+    let mut high = 2.0;
+    while distance(high) > threshold {
+        low = high;
+        high *= 2.0;
+    }
+
+    for _ in 0..100 {
+        let mid = 0.5 * (low + high);
+        if distance(mid) > threshold {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    return r.clone() * high;
+}
@@ -0,0 +1,168 @@
+use std::f64::consts::PI;
+
+use peroxide::{
+    fuga::LinearAlgebra,
+    prelude::{zeros, Matrix},
+};
+use rand::Rng;
+use rand_distr::{Distribution, StandardNormal};
+
+use crate::linalg::{cholesky_semidefinite, solve_lower, submatrix};
+
+/// Gaussian distribution over column vectors, held with the Cholesky factor of
+/// its covariance. Sampling works for any positive semi-definite covariance;
+/// the density, Mahalanobis distance and conditioning need it to be positive
+/// definite.
+#[derive(Clone, Debug)]
+pub struct MultivariateNormal {
+    pub mean: Matrix,
+    pub cov: Matrix,
+    l: Matrix,
+}
+
+impl MultivariateNormal {
+    pub fn new(mean: Matrix, cov: Matrix) -> MultivariateNormal {
+        assert_eq!(mean.row, cov.row, "Mean and covariance sizes differ");
+        let l = cholesky_semidefinite(&cov);
+        return MultivariateNormal { mean, cov, l };
+    }
+
+    pub fn zero_mean(cov: Matrix) -> MultivariateNormal {
+        return MultivariateNormal::new(zeros(cov.row, 1), cov);
+    }
+
+    pub fn dim(&self) -> usize {
+        return self.mean.row;
+    }
+
+    /// Lower triangular `L` with `L L' = cov`
+    pub fn cholesky(&self) -> &Matrix {
+        return &self.l;
+    }
+
+    /// True when the covariance is only positive semi-definite
+    pub fn is_singular(&self) -> bool {
+        return (0..self.dim()).any(|i| self.l[(i, i)] == 0.0);
+    }
+
+    /// `mean + L u` with `u` standard normal
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Matrix {
+        let mut u = zeros(self.dim(), 1);
+        for i in 0..self.dim() {
+            u[(i, 0)] = StandardNormal.sample(rng);
+        }
+        return &self.mean + &(&self.l * &u);
+    }
+
+    pub fn sample_n<R: Rng>(&self, n: usize, rng: &mut R) -> Vec<Matrix> {
+        return (0..n).map(|_| self.sample(rng)).collect();
+    }
+
+    /// `(x - mean)' cov^-1 (x - mean)`
+    pub fn mahalanobis_squared(&self, x: &Matrix) -> f64 {
+        self.assert_non_singular();
+        let whitened = solve_lower(&self.l, &(x - &self.mean));
+        return whitened.data.iter().map(|value| value.powf(2.0)).sum();
+    }
+
+    pub fn mahalanobis(&self, x: &Matrix) -> f64 {
+        return self.mahalanobis_squared(x).sqrt();
+    }
+
+    pub fn log_pdf(&self, x: &Matrix) -> f64 {
+        let log_det: f64 = (0..self.dim()).map(|i| 2.0 * self.l[(i, i)].ln()).sum();
+        return -0.5
+            * (self.dim() as f64 * (2.0 * PI).ln() + log_det + self.mahalanobis_squared(x));
+    }
+
+    pub fn pdf(&self, x: &Matrix) -> f64 {
+        return self.log_pdf(x).exp();
+    }
+
+    /// Distribution of the states in `indices`, in that order
+    pub fn marginal(&self, indices: &[usize]) -> MultivariateNormal {
+        return MultivariateNormal::new(
+            submatrix(&self.mean, indices, &[0]),
+            submatrix(&self.cov, indices, indices),
+        );
+    }
+
+    /// Distribution of the remaining states, in order, given that the states
+    /// in `indices` take `values`
+    pub fn condition(&self, indices: &[usize], values: &Matrix) -> MultivariateNormal {
+        self.assert_non_singular();
+        let rest: Vec<usize> = (0..self.dim()).filter(|i| !indices.contains(i)).collect();
+
+        let cov_ab = submatrix(&self.cov, &rest, indices);
+        let cov_bb = submatrix(&self.cov, indices, indices);
+        let gain = &cov_ab * &cov_bb.inv();
+
+        let offset = values - &submatrix(&self.mean, indices, &[0]);
+        let mean = &submatrix(&self.mean, &rest, &[0]) + &(&gain * &offset);
+        let cov = &submatrix(&self.cov, &rest, &rest) - &(&gain * &cov_ab.t());
+
+        return MultivariateNormal::new(mean, cov);
+    }
+
+    fn assert_non_singular(&self) {
+        assert!(
+            !self.is_singular(),
+            "The covariance must be positive definite"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use peroxide::prelude::{matrix, Shape::Row};
+
+    use super::*;
+    use crate::rng::seeded_rng;
+
+    #[test]
+    fn sample_covariance_matches() {
+        let cov = matrix(vec![4.0, 1.2, 1.2, 1.0], 2, 2, Row);
+        let normal = MultivariateNormal::new(matrix(vec![1.0, -2.0], 2, 1, Row), cov.clone());
+        let n = 20_000;
+        let samples = normal.sample_n(n, &mut seeded_rng(7));
+
+        let mut mean = zeros(2, 1);
+        for sample in &samples {
+            mean = &mean + sample;
+        }
+        mean = mean * (1.0 / n as f64);
+
+        let mut sample_cov = zeros(2, 2);
+        for sample in &samples {
+            let error = sample - &mean;
+            sample_cov = &sample_cov + &(&error * &error.t());
+        }
+        sample_cov = sample_cov * (1.0 / (n - 1) as f64);
+
+        // About five standard errors for this many samples
+        for i in 0..2 {
+            assert!((mean[(i, 0)] - normal.mean[(i, 0)]).abs() < 0.08);
+            for j in 0..2 {
+                assert!(
+                    (sample_cov[(i, j)] - cov[(i, j)]).abs() < 0.2,
+                    "Sample covariance ({}, {}) was {}, expected {}",
+                    i,
+                    j,
+                    sample_cov[(i, j)],
+                    cov[(i, j)]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn singular_covariance_samples_stay_on_its_range() {
+        let cov = matrix(vec![1.0, 1.0, 1.0, 1.0], 2, 2, Row);
+        let normal = MultivariateNormal::zero_mean(cov);
+        assert!(normal.is_singular());
+
+        for sample in normal.sample_n(100, &mut seeded_rng(7)) {
+            assert!((sample[(0, 0)] - sample[(1, 0)]).abs() < 1e-12);
+        }
+    }
+}
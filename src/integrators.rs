@@ -0,0 +1,294 @@
+use std::fmt;
+
+use peroxide::prelude::Matrix;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IntegrationStats {
+    pub accepted: usize,
+    pub rejected: usize,
+    pub evaluations: usize,
+}
+
+/// Why an adaptive integration gave up before reaching its end time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntegrationError {
+    /// `max_steps` accepted and rejected steps were taken, ending at `t`
+    TooManySteps { max_steps: usize, t: f64 },
+    /// A step of `min_step` was rejected at `t`, usually because the
+    /// dynamics are stiff or not smooth there
+    StepTooSmall { min_step: f64, t: f64 },
+}
+
+impl fmt::Display for IntegrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            IntegrationError::TooManySteps { max_steps, t } => {
+                write!(f, "Exceeded {} integration steps at t = {}", max_steps, t)
+            }
+            IntegrationError::StepTooSmall { min_step, t } => {
+                write!(f, "Step size fell below {} at t = {}", min_step, t)
+            }
+        };
+    }
+}
+
+impl std::error::Error for IntegrationError {}
+
+/// Classic fixed step fourth-order Runge-Kutta over `steps` equal steps from
+/// `t0` to `t1`. The state may be any shape of matrix.
+pub fn rk4<F>(f: F, t0: f64, x0: &Matrix, t1: f64, steps: usize) -> (Matrix, IntegrationStats)
+where
+    F: Fn(f64, &Matrix) -> Matrix,
+{
+    let h = (t1 - t0) / steps as f64;
+    let mut t = t0;
+    let mut x = x0.clone();
+
+    for _ in 0..steps {
+        let k1 = f(t, &x);
+        let k2 = f(t + 0.5 * h, &combine(&x, &[(0.5 * h, &k1)]));
+        let k3 = f(t + 0.5 * h, &combine(&x, &[(0.5 * h, &k2)]));
+        let k4 = f(t + h, &combine(&x, &[(h, &k3)]));
+
+        x = combine(
+            &x,
+            &[
+                (h / 6.0, &k1),
+                (h / 3.0, &k2),
+                (h / 3.0, &k3),
+                (h / 6.0, &k4),
+            ],
+        );
+        t += h;
+    }
+
+    let stats = IntegrationStats {
+        accepted: steps,
+        rejected: 0,
+        evaluations: 4 * steps,
+    };
+
+    return (x, stats);
+}
+
+/// Adaptive Dormand-Prince 5(4) integrator
+#[derive(Clone, Copy, Debug)]
+pub struct Rk45 {
+    pub rtol: f64,
+    pub atol: f64,
+    /// First step to try; defaults to a hundredth of the interval
+    pub initial_step: Option<f64>,
+    pub min_step: f64,
+    pub max_step: f64,
+    pub max_steps: usize,
+}
+
+impl Default for Rk45 {
+    fn default() -> Self {
+        return Rk45 {
+            rtol: 1e-6,
+            atol: 1e-9,
+            initial_step: None,
+            min_step: 1e-12,
+            max_step: f64::INFINITY,
+            max_steps: 100_000,
+        };
+    }
+}
+
+const C: [f64; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+const A: [[f64; 6]; 7] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [
+        19372.0 / 6561.0,
+        -25360.0 / 2187.0,
+        64448.0 / 6561.0,
+        -212.0 / 729.0,
+        0.0,
+        0.0,
+    ],
+    [
+        9017.0 / 3168.0,
+        -355.0 / 33.0,
+        46732.0 / 5247.0,
+        49.0 / 176.0,
+        -5103.0 / 18656.0,
+        0.0,
+    ],
+    [
+        35.0 / 384.0,
+        0.0,
+        500.0 / 1113.0,
+        125.0 / 192.0,
+        -2187.0 / 6784.0,
+        11.0 / 84.0,
+    ],
+];
+// Difference between the fifth and fourth order weights
+const E: [f64; 7] = [
+    71.0 / 57600.0,
+    0.0,
+    -71.0 / 16695.0,
+    71.0 / 1920.0,
+    -17253.0 / 339200.0,
+    22.0 / 525.0,
+    -1.0 / 40.0,
+];
+
+impl Rk45 {
+    pub fn new(rtol: f64, atol: f64) -> Rk45 {
+        return Rk45 {
+            rtol,
+            atol,
+            ..Rk45::default()
+        };
+    }
+
+    /// State at `t1`, or an error if the step limits are hit first
+    pub fn integrate<F>(
+        &self,
+        f: F,
+        t0: f64,
+        x0: &Matrix,
+        t1: f64,
+    ) -> Result<(Matrix, IntegrationStats), IntegrationError>
+    where
+        F: Fn(f64, &Matrix) -> Matrix,
+    {
+        assert!(t1 >= t0, "Can only integrate forwards in time");
+
+        let mut stats = IntegrationStats::default();
+        let mut t = t0;
+        let mut x = x0.clone();
+        let mut h = self
+            .initial_step
+            .unwrap_or((t1 - t0) / 100.0)
+            .min(self.max_step);
+
+        while t < t1 {
+            if stats.accepted + stats.rejected >= self.max_steps {
+                return Err(IntegrationError::TooManySteps {
+                    max_steps: self.max_steps,
+                    t,
+                });
+            }
+            h = h.min(t1 - t);
+
+            let mut k: Vec<Matrix> = Vec::with_capacity(7);
+            for stage in 0..7 {
+                let terms: Vec<(f64, &Matrix)> = k
+                    .iter()
+                    .enumerate()
+                    .map(|(j, k_j)| (h * A[stage][j], k_j))
+                    .collect();
+                let x_stage = combine(&x, &terms);
+                k.push(f(t + C[stage] * h, &x_stage));
+            }
+            stats.evaluations += 7;
+
+            // The last stage is evaluated at the fifth order solution
+            let x_new = combine(
+                &x,
+                &(0..6).map(|j| (h * A[6][j], &k[j])).collect::<Vec<_>>(),
+            );
+            let error = combine(
+                &(x.clone() * 0.0),
+                &(0..7).map(|j| (h * E[j], &k[j])).collect::<Vec<_>>(),
+            );
+
+            let norm = self.error_norm(&x, &x_new, &error);
+            if norm <= 1.0 {
+                t += h;
+                x = x_new;
+                stats.accepted += 1;
+            } else {
+                stats.rejected += 1;
+                if h <= self.min_step {
+                    return Err(IntegrationError::StepTooSmall {
+                        min_step: self.min_step,
+                        t,
+                    });
+                }
+            }
+
+            let factor = if norm == 0.0 {
+                5.0
+            } else {
+                (0.9 * norm.powf(-0.2)).clamp(0.2, 5.0)
+            };
+            h = (h * factor).max(self.min_step).min(self.max_step);
+        }
+
+        return Ok((x, stats));
+    }
+
+    fn error_norm(&self, x: &Matrix, x_new: &Matrix, error: &Matrix) -> f64 {
+        let mut sum = 0.0;
+        for i in 0..x.row {
+            for j in 0..x.col {
+                let scale = self.atol + self.rtol * x[(i, j)].abs().max(x_new[(i, j)].abs());
+                sum += (error[(i, j)] / scale).powf(2.0);
+            }
+        }
+        return (sum / (x.row * x.col) as f64).sqrt();
+    }
+}
+
+/// `x + sum(a_i * k_i)`, element by element
+fn combine(x: &Matrix, terms: &[(f64, &Matrix)]) -> Matrix {
+    let mut out = x.clone();
+    for i in 0..x.row {
+        for j in 0..x.col {
+            for (a, k) in terms {
+                out[(i, j)] += a * k[(i, j)];
+            }
+        }
+    }
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use peroxide::prelude::{matrix, Shape::Row};
+
+    use super::*;
+
+    #[test]
+    fn rk45_integrates_exponential_growth() {
+        let x0 = matrix(vec![1.0], 1, 1, Row);
+        let integrator = Rk45::new(1e-8, 1e-10);
+
+        let (x, stats) = integrator
+            .integrate(|_t, x: &Matrix| x.clone(), 0.0, &x0, 2.0)
+            .unwrap();
+
+        let expected = 2.0f64.exp();
+        assert!(
+            (x[(0, 0)] - expected).abs() < 1e-6 * expected,
+            "Integrated to {}, expected {}",
+            x[(0, 0)],
+            expected
+        );
+        assert!(stats.accepted > 1);
+        assert_eq!(stats.evaluations, 7 * (stats.accepted + stats.rejected));
+    }
+
+    #[test]
+    fn rk45_reports_too_many_steps() {
+        let x0 = matrix(vec![1.0], 1, 1, Row);
+        let integrator = Rk45 {
+            max_steps: 3,
+            ..Rk45::new(1e-8, 1e-10)
+        };
+
+        let result = integrator.integrate(|_t, x: &Matrix| x.clone(), 0.0, &x0, 2.0);
+
+        assert!(matches!(
+            result,
+            Err(IntegrationError::TooManySteps { max_steps: 3, .. })
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+use peroxide::{
+    fuga::LinearAlgebra,
+    prelude::{eye, zeros, Matrix},
+};
+
+use crate::{
+    integrators::rk4,
+    linalg::{pack_state, symmetrise, unpack_state},
+    Convergence,
+};
+
+/// Continuous-time Kalman filter for `x_dot = F x + w` observed continuously
+/// through `z = H x + v`, with `q` and `r` the spectral densities of `w` and
+/// `v`. The estimate follows `x_hat_dot = F x_hat + K (z - H x_hat)` and the
+/// covariance the Riccati equation `P_dot = F P + P F' + Q - P H' R^-1 H P`.
+pub struct KalmanBucy {
+    pub x: Matrix,
+    pub p: Matrix,
+    pub t: f64,
+    pub f: Matrix,
+    pub h: Matrix,
+    pub q: Matrix,
+    pub r: Matrix,
+    /// RK4 steps taken per call to `step`
+    pub substeps: usize,
+}
+
+impl KalmanBucy {
+    pub fn new(
+        x: Matrix,
+        p: Matrix,
+        t: f64,
+        f: Matrix,
+        h: Matrix,
+        q: Matrix,
+        r: Matrix,
+    ) -> KalmanBucy {
+        return KalmanBucy {
+            x,
+            p,
+            t,
+            f,
+            h,
+            q,
+            r,
+            substeps: 1,
+        };
+    }
+
+    /// `K = P H' R^-1`
+    pub fn gain(&self) -> Matrix {
+        return &(&self.p * &self.h.t()) * &self.r.inv();
+    }
+
+    /// Advances the filter by `dt`, holding the measurement `z` over the
+    /// interval, and returns the gain at the end of it
+    pub fn step(&mut self, z: &Matrix, dt: f64) -> Matrix {
+        let n = self.x.row;
+        let r_inv = self.r.inv();
+
+        let derivative = |_: f64, packed: &Matrix| {
+            let (x, p) = unpack_state(packed, n);
+            let k = &(&p * &self.h.t()) * &r_inv;
+            let x_dot = &(&self.f * &x) + &(&k * &(z - &(&self.h * &x)));
+            let p_dot =
+                &(&(&(&self.f * &p) + &(&p * &self.f.t())) + &self.q) - &(&(&k * &self.h) * &p);
+            return pack_state(&x_dot, &p_dot);
+        };
+
+        let (packed, _) = rk4(
+            derivative,
+            self.t,
+            &pack_state(&self.x, &self.p),
+            self.t + dt,
+            self.substeps,
+        );
+        let (x, p) = unpack_state(&packed, n);
+
+        self.x = x;
+        self.p = symmetrise(&p);
+        self.t += dt;
+
+        return self.gain();
+    }
+}
+
+/// Steady state covariance of the Kalman-Bucy filter, the solution of the
+/// continuous algebraic Riccati equation `F P + P F' + Q - P H' R^-1 H P = 0`.
+/// Found with the matrix sign function of the Hamiltonian, so it needs the
+/// system to be detectable and stabilisable. Returns `None` if the sign
+/// iteration does not converge.
+pub fn solve_care(
+    f: &Matrix,
+    h: &Matrix,
+    q: &Matrix,
+    r: &Matrix,
+    convergence: &Convergence,
+) -> Option<Matrix> {
+    let n = f.row;
+    let g = &(&h.t() * &r.inv()) * h;
+
+    // Hamiltonian of the equivalent control problem with A = F'
+    let mut z = zeros(2 * n, 2 * n);
+    for i in 0..n {
+        for j in 0..n {
+            z[(i, j)] = f[(j, i)];
+            z[(i, n + j)] = -g[(i, j)];
+            z[(n + i, j)] = -q[(i, j)];
+            z[(n + i, n + j)] = -f[(i, j)];
+        }
+    }
+
+    let mut converged = false;
+    for _ in 0..convergence.max_iterations {
+        // Determinant scaling speeds up the early iterations
+        let c = z.det().abs().powf(1.0 / (2 * n) as f64);
+        let next = &(z.clone() * (0.5 / c)) + &(z.inv() * (0.5 * c));
+
+        let change = max_abs(&(&next - &z));
+        let size = max_abs(&z);
+        z = next;
+        if change <= convergence.tolerance * size {
+            converged = true;
+            break;
+        }
+    }
+    if !converged {
+        return None;
+    }
+
+    // P solves [W12; W22 + I] P = -[W11 + I; W21] in the least squares sense
+    let mut lhs = zeros(2 * n, n);
+    let mut rhs = zeros(2 * n, n);
+    for i in 0..2 * n {
+        for j in 0..n {
+            lhs[(i, j)] = z[(i, n + j)];
+            rhs[(i, j)] = -z[(i, j)];
+        }
+    }
+    let identity = eye(n);
+    for i in 0..n {
+        for j in 0..n {
+            lhs[(n + i, j)] += identity[(i, j)];
+            rhs[(i, j)] -= identity[(i, j)];
+        }
+    }
+
+    let p = &(&(&lhs.t() * &lhs).inv() * &lhs.t()) * &rhs;
+    return Some(symmetrise(&p));
+}
+
+/// Steady state Kalman-Bucy gain `P H' R^-1`
+pub fn steady_state_gain(
+    f: &Matrix,
+    h: &Matrix,
+    q: &Matrix,
+    r: &Matrix,
+    convergence: &Convergence,
+) -> Option<Matrix> {
+    let p = solve_care(f, h, q, r, convergence)?;
+    return Some(&(&p * &h.t()) * &r.inv());
+}
+
+fn max_abs(a: &Matrix) -> f64 {
+    return a.data.iter().fold(0.0, |max, value| value.abs().max(max));
+}
+
+#[cfg(test)]
+mod tests {
+    use peroxide::prelude::{matrix, Shape::Row};
+
+    use super::*;
+
+    #[test]
+    fn solve_care_matches_scalar_riccati() {
+        // (f, h, q, r) for a stable, an unstable and a marginal system
+        let systems = [
+            (-1.0, 1.0, 2.0, 0.5),
+            (0.5, 2.0, 1.0, 3.0),
+            (0.0, 1.0, 1.0, 1.0),
+        ];
+        for (f, h, q, r) in systems {
+            let p = solve_care(
+                &matrix(vec![f], 1, 1, Row),
+                &matrix(vec![h], 1, 1, Row),
+                &matrix(vec![q], 1, 1, Row),
+                &matrix(vec![r], 1, 1, Row),
+                &Convergence::default(),
+            )
+            .unwrap();
+
+            // Positive root of 2 f P + q - P^2 h^2 / r = 0
+            let expected = r / h.powf(2.0) * (f + (f.powf(2.0) + h.powf(2.0) * q / r).sqrt());
+            assert!(
+                (p[(0, 0)] - expected).abs() < 1e-8 * expected,
+                "Solved P = {} for f = {}, expected {}",
+                p[(0, 0)],
+                f,
+                expected
+            );
+        }
+    }
+}
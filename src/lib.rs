@@ -1,5 +1,32 @@
 use std::{fs::File, io::Write};
 
+pub mod autodiff;
+pub mod consistency;
+pub mod constraints;
+pub mod continuous_discrete;
+pub mod covariance_analysis;
+pub mod discretisation;
+pub mod ekf;
+pub mod filter;
+pub mod fusion;
+pub mod gating;
+pub mod gaussian;
+pub mod integrators;
+pub mod kalman_bucy;
+pub mod linalg;
+pub mod logs;
+pub mod models;
+pub mod monte_carlo;
+pub mod oosm;
+pub mod plotting;
+pub mod report;
+pub mod rng;
+pub mod robust;
+pub mod sequential;
+pub mod simulation;
+pub mod stats;
+pub mod svg;
+
 use peroxide::{
     fuga::LinearAlgebra,
     prelude::{eye, Matrix},
@@ -10,7 +37,11 @@ pub fn make_m(phi: &Matrix, p: &Matrix, q: &Matrix) -> Matrix {
 }
 
 pub fn make_k(m: &Matrix, h: &Matrix, r: &Matrix) -> Matrix {
-    return (m * &h.t()) * (&(h * m * h.t()) + r).inv();
+    return (m * &h.t()) * make_s(m, h, r).inv();
+}
+
+pub fn make_s(m: &Matrix, h: &Matrix, r: &Matrix) -> Matrix {
+    return &(h * m * h.t()) + r;
 }
 
 pub fn new_cov(k: &Matrix, h: &Matrix, m: &Matrix) -> Matrix {
@@ -18,6 +49,20 @@ pub fn new_cov(k: &Matrix, h: &Matrix, m: &Matrix) -> Matrix {
     return &(eye(s.col) - s) * m;
 }
 
+pub struct Convergence {
+    pub max_iterations: usize,
+    pub tolerance: f64,
+}
+
+impl Default for Convergence {
+    fn default() -> Self {
+        return Convergence {
+            max_iterations: 20,
+            tolerance: 1e-6,
+        };
+    }
+}
+
 pub fn write_to_file(file_name: &str, content: &String) {
     let mut file = File::create(file_name).expect("Failed to create file");
     file.write_all(content.as_bytes())
@@ -0,0 +1,186 @@
+use peroxide::prelude::{zeros, Matrix};
+
+/// Lower triangular `l` with `l l' = a`, or `None` if `a` is not positive
+/// definite
+pub fn cholesky(a: &Matrix) -> Option<Matrix> {
+    let n = a.row;
+    let mut l = zeros(n, n);
+
+    for j in 0..n {
+        let mut d = a[(j, j)];
+        for k in 0..j {
+            d -= l[(j, k)].powf(2.0);
+        }
+        if d <= 0.0 {
+            return None;
+        }
+        l[(j, j)] = d.sqrt();
+
+        for i in j + 1..n {
+            let mut s = a[(i, j)];
+            for k in 0..j {
+                s -= l[(i, k)] * l[(j, k)];
+            }
+            l[(i, j)] = s / l[(j, j)];
+        }
+    }
+
+    return Some(l);
+}
+
+/// Lower triangular `l` with `l l' = a` for positive semi-definite `a`.
+/// Columns whose pivot is not positive are left at zero, so a covariance with
+/// noise-free states, such as a process noise `Q` acting on velocity only,
+/// still factorises.
+pub fn cholesky_semidefinite(a: &Matrix) -> Matrix {
+    let n = a.row;
+    let mut l = zeros(n, n);
+    let scale = (0..n).map(|i| a[(i, i)].abs()).fold(0.0, f64::max);
+
+    for j in 0..n {
+        let mut d = a[(j, j)];
+        for k in 0..j {
+            d -= l[(j, k)].powf(2.0);
+        }
+        if d <= 1e-12 * scale {
+            continue;
+        }
+        l[(j, j)] = d.sqrt();
+
+        for i in j + 1..n {
+            let mut s = a[(i, j)];
+            for k in 0..j {
+                s -= l[(i, k)] * l[(j, k)];
+            }
+            l[(i, j)] = s / l[(j, j)];
+        }
+    }
+
+    return l;
+}
+
+/// Solves `l x = b` for lower triangular `l` by forward substitution
+pub fn solve_lower(l: &Matrix, b: &Matrix) -> Matrix {
+    let n = l.row;
+    let mut x = zeros(n, b.col);
+
+    for c in 0..b.col {
+        for i in 0..n {
+            let mut s = b[(i, c)];
+            for k in 0..i {
+                s -= l[(i, k)] * x[(k, c)];
+            }
+            x[(i, c)] = s / l[(i, i)];
+        }
+    }
+
+    return x;
+}
+
+/// Sum of the diagonal of a square matrix
+pub fn trace(a: &Matrix) -> f64 {
+    return (0..a.row).map(|i| a[(i, i)]).sum();
+}
+
+pub fn is_diagonal(a: &Matrix) -> bool {
+    for i in 0..a.row {
+        for j in 0..a.col {
+            if i != j && a[(i, j)] != 0.0 {
+                return false;
+            }
+        }
+    }
+    return true;
+}
+
+pub fn row_matrix(a: &Matrix, i: usize) -> Matrix {
+    let mut row = zeros(1, a.col);
+    for j in 0..a.col {
+        row[(0, j)] = a[(i, j)];
+    }
+    return row;
+}
+
+/// `[a; b]`
+pub fn stack_rows(a: &Matrix, b: &Matrix) -> Matrix {
+    assert_eq!(
+        a.col, b.col,
+        "Stacked matrices need the same number of columns"
+    );
+    let mut out = zeros(a.row + b.row, a.col);
+    for j in 0..a.col {
+        for i in 0..a.row {
+            out[(i, j)] = a[(i, j)];
+        }
+        for i in 0..b.row {
+            out[(a.row + i, j)] = b[(i, j)];
+        }
+    }
+    return out;
+}
+
+/// `[a 0; 0 b]`
+pub fn block_diagonal(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut out = zeros(a.row + b.row, a.col + b.col);
+    for i in 0..a.row {
+        for j in 0..a.col {
+            out[(i, j)] = a[(i, j)];
+        }
+    }
+    for i in 0..b.row {
+        for j in 0..b.col {
+            out[(a.row + i, a.col + j)] = b[(i, j)];
+        }
+    }
+    return out;
+}
+
+/// Stacks a state and the rows of its covariance into one column, so both can
+/// be integrated together
+pub fn pack_state(x: &Matrix, p: &Matrix) -> Matrix {
+    let n = x.row;
+    let mut packed = zeros(n + n * n, 1);
+    for i in 0..n {
+        packed[(i, 0)] = x[(i, 0)];
+        for j in 0..n {
+            packed[(n + i * n + j, 0)] = p[(i, j)];
+        }
+    }
+    return packed;
+}
+
+/// Inverse of `pack_state` for a state of dimension `n`
+pub fn unpack_state(packed: &Matrix, n: usize) -> (Matrix, Matrix) {
+    let mut x = zeros(n, 1);
+    let mut p = zeros(n, n);
+    for i in 0..n {
+        x[(i, 0)] = packed[(i, 0)];
+        for j in 0..n {
+            p[(i, j)] = packed[(n + i * n + j, 0)];
+        }
+    }
+    return (x, p);
+}
+
+/// `(p + p') / 2`, removing the asymmetry numerical error builds up in a
+/// covariance
+pub fn symmetrise(p: &Matrix) -> Matrix {
+    let mut out = p.clone();
+    for i in 0..p.row {
+        for j in 0..p.col {
+            out[(i, j)] = 0.5 * (p[(i, j)] + p[(j, i)]);
+        }
+    }
+    return out;
+}
+
+/// Rows `rows` and columns `cols` of `a`, in the given order
+pub fn submatrix(a: &Matrix, rows: &[usize], cols: &[usize]) -> Matrix {
+    let mut out = zeros(rows.len(), cols.len());
+    for (i, row) in rows.iter().enumerate() {
+        for (j, col) in cols.iter().enumerate() {
+            out[(i, j)] = a[(*row, *col)];
+        }
+    }
+    return out;
+}
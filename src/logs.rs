@@ -0,0 +1,364 @@
+use std::{fmt, fs::File, io, path::Path};
+
+use csv::{ReaderBuilder, StringRecord, Trim, Writer};
+use peroxide::prelude::{matrix, zeros, Matrix, Shape::Row};
+
+use crate::{
+    filter::StepRecord,
+    fusion::{SensorFusion, SensorMeasurement},
+};
+
+#[derive(Debug)]
+pub enum LogError {
+    Csv(csv::Error),
+    Io(io::Error),
+    /// A mapped column is not in the header
+    MissingColumn(String),
+    /// A cell that should hold a number is empty or does not parse. `row`
+    /// counts data rows from 1, excluding the header.
+    InvalidValue {
+        row: usize,
+        column: String,
+        value: String,
+    },
+    /// A row names no sensor registered with the fusion filter. `sensor` is
+    /// empty when the row has no sensor name.
+    UnknownSensor {
+        row: usize,
+        sensor: String,
+    },
+    /// A row's measurement does not have the size its sensor expects
+    WrongSize {
+        row: usize,
+        sensor: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            LogError::Csv(e) => write!(f, "CSV error: {}", e),
+            LogError::Io(e) => write!(f, "IO error: {}", e),
+            LogError::MissingColumn(column) => write!(f, "No column named '{}'", column),
+            LogError::InvalidValue { row, column, value } if value.is_empty() => {
+                write!(f, "Row {}: column '{}' is empty", row, column)
+            }
+            LogError::InvalidValue { row, column, value } => write!(
+                f,
+                "Row {}: '{}' in column '{}' is not a number",
+                row, value, column
+            ),
+            LogError::UnknownSensor { row, sensor } if sensor.is_empty() => {
+                write!(f, "Row {}: no sensor given", row)
+            }
+            LogError::UnknownSensor { row, sensor } => {
+                write!(f, "Row {}: no sensor named '{}'", row, sensor)
+            }
+            LogError::WrongSize {
+                row,
+                sensor,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Row {}: {} takes {} components, found {}",
+                row, sensor, expected, found
+            ),
+        };
+    }
+}
+
+impl std::error::Error for LogError {}
+
+impl From<csv::Error> for LogError {
+    fn from(e: csv::Error) -> LogError {
+        return LogError::Csv(e);
+    }
+}
+
+impl From<io::Error> for LogError {
+    fn from(e: io::Error) -> LogError {
+        return LogError::Io(e);
+    }
+}
+
+/// Header names of the columns holding each part of a measurement
+#[derive(Clone, Debug)]
+pub struct ColumnMapping {
+    pub timestamp: String,
+    pub components: Vec<String>,
+    pub sensor: Option<String>,
+    /// Either one variance column per component, giving a diagonal `R`, or a
+    /// single column used for every component
+    pub variances: Vec<String>,
+}
+
+impl ColumnMapping {
+    pub fn new(timestamp: &str, components: &[&str]) -> ColumnMapping {
+        return ColumnMapping {
+            timestamp: timestamp.to_string(),
+            components: components.iter().map(|c| c.to_string()).collect(),
+            sensor: None,
+            variances: vec![],
+        };
+    }
+
+    pub fn with_sensor(mut self, column: &str) -> ColumnMapping {
+        self.sensor = Some(column.to_string());
+        return self;
+    }
+
+    pub fn with_variances(mut self, columns: &[&str]) -> ColumnMapping {
+        assert!(
+            columns.len() == 1 || columns.len() == self.components.len(),
+            "Give one variance column, or one per component"
+        );
+        self.variances = columns.iter().map(|c| c.to_string()).collect();
+        return self;
+    }
+}
+
+/// One row of a measurement log
+#[derive(Clone, Debug)]
+pub struct MeasurementRecord {
+    /// Data row in the log, counting from 1 and excluding the header
+    pub row: usize,
+    pub t: f64,
+    pub sensor: Option<String>,
+    /// `None` when every component cell is empty, such as during an outage.
+    /// Trailing empty cells are left out, so `z` can be shorter than the
+    /// mapped components.
+    pub z: Option<Matrix>,
+    /// Diagonal measurement noise from the variance columns, `None` when they
+    /// are not mapped or empty
+    pub r: Option<Matrix>,
+}
+
+impl MeasurementRecord {
+    /// Measurement for `fusion`, addressed to the sensor registered under this
+    /// record's sensor name. `None` if the record has no measurement, and an
+    /// error if it names no known sensor or has the wrong number of components
+    /// for it.
+    pub fn to_sensor_measurement(
+        &self,
+        fusion: &SensorFusion,
+    ) -> Result<Option<SensorMeasurement>, LogError> {
+        let name = self.sensor.clone().unwrap_or_default();
+        let sensor = fusion
+            .sensor_id(&name)
+            .ok_or_else(|| LogError::UnknownSensor {
+                row: self.row,
+                sensor: name.clone(),
+            })?;
+        let z = match &self.z {
+            Some(z) => z.clone(),
+            None => return Ok(None),
+        };
+
+        let expected = fusion.sensors[sensor].r.row;
+        if z.row != expected {
+            return Err(LogError::WrongSize {
+                row: self.row,
+                sensor: name,
+                expected,
+                found: z.row,
+            });
+        }
+
+        return Ok(Some(SensorMeasurement {
+            sensor,
+            t: self.t,
+            z,
+            r: self.r.clone(),
+        }));
+    }
+}
+
+/// Timestamps and measurements in the form `KalmanFilter::run` takes
+pub fn filter_input(records: &[MeasurementRecord]) -> Vec<(f64, Option<Matrix>)> {
+    return records
+        .iter()
+        .map(|record| (record.t, record.z.clone()))
+        .collect();
+}
+
+/// Reads a measurement log with a header row. Cells are trimmed, and columns
+/// not in `mapping` are ignored. A row whose variance cells are filled for the
+/// components it has gets a diagonal `r`.
+pub fn read_measurements<R: io::Read>(
+    reader: R,
+    mapping: &ColumnMapping,
+) -> Result<Vec<MeasurementRecord>, LogError> {
+    let mut reader = ReaderBuilder::new().trim(Trim::All).from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let find = |column: &String| {
+        return headers
+            .iter()
+            .position(|header| header == column)
+            .ok_or_else(|| LogError::MissingColumn(column.clone()));
+    };
+
+    let timestamp = find(&mapping.timestamp)?;
+    let sensor = mapping.sensor.as_ref().map(find).transpose()?;
+    let components = mapping
+        .components
+        .iter()
+        .map(find)
+        .collect::<Result<Vec<usize>, LogError>>()?;
+    let variances = mapping
+        .variances
+        .iter()
+        .map(find)
+        .collect::<Result<Vec<usize>, LogError>>()?;
+
+    let mut records = vec![];
+    for (i, row) in reader.records().enumerate() {
+        let row = row?;
+        let cell = |index: usize| parse_cell(&row, index, i + 1, &headers);
+
+        let t = cell(timestamp)?.ok_or_else(|| LogError::InvalidValue {
+            row: i + 1,
+            column: mapping.timestamp.clone(),
+            value: String::new(),
+        })?;
+
+        let values = components
+            .iter()
+            .map(|index| cell(*index))
+            .collect::<Result<Vec<Option<f64>>, LogError>>()?;
+        // Trailing empty cells are dropped, so sensors of different sizes can
+        // share a log
+        let size = values
+            .iter()
+            .rposition(|value| value.is_some())
+            .map_or(0, |last| last + 1);
+        let z = if size == 0 {
+            None
+        } else {
+            let values = components[..size]
+                .iter()
+                .zip(values.iter())
+                .map(|(index, value)| {
+                    return value.ok_or_else(|| LogError::InvalidValue {
+                        row: i + 1,
+                        column: headers[*index].to_string(),
+                        value: String::new(),
+                    });
+                })
+                .collect::<Result<Vec<f64>, LogError>>()?;
+            Some(matrix(values, size, 1, Row))
+        };
+
+        // Only the variances of the components present need to be filled
+        let variance_columns = match variances.len() {
+            1 => &variances[..],
+            _ => &variances[..size.min(variances.len())],
+        };
+        let variance_values = variance_columns
+            .iter()
+            .map(|index| cell(*index))
+            .collect::<Result<Option<Vec<f64>>, LogError>>()?;
+        let r = match variance_values {
+            Some(values) if !values.is_empty() && size > 0 => {
+                let mut r = zeros(size, size);
+                for j in 0..size {
+                    r[(j, j)] = values[j.min(values.len() - 1)];
+                }
+                Some(r)
+            }
+            _ => None,
+        };
+
+        records.push(MeasurementRecord {
+            row: i + 1,
+            t,
+            sensor: sensor.map(|index| row[index].to_string()),
+            z,
+            r,
+        });
+    }
+
+    return Ok(records);
+}
+
+pub fn read_measurements_from_path<P: AsRef<Path>>(
+    path: P,
+    mapping: &ColumnMapping,
+) -> Result<Vec<MeasurementRecord>, LogError> {
+    return read_measurements(File::open(path)?, mapping);
+}
+
+/// Writes one row per step with the prior and posterior states, the diagonal
+/// of the posterior covariance, the innovation and the gain (row by row).
+/// Steps without a measurement leave the innovation and gain cells empty, as
+/// do measurements smaller than the largest one in the history.
+pub fn write_history<W: io::Write>(writer: W, history: &[StepRecord]) -> Result<(), LogError> {
+    let mut writer = Writer::from_writer(writer);
+    let n = history
+        .first()
+        .map(|record| record.posterior.row)
+        .unwrap_or(0);
+    let m = history
+        .iter()
+        .filter_map(|record| record.innovation.as_ref().map(|v| v.row))
+        .max()
+        .unwrap_or(0);
+
+    let mut header = vec!["t".to_string()];
+    header.extend((0..n).map(|i| format!("prior_{}", i)));
+    header.extend((0..n).map(|i| format!("posterior_{}", i)));
+    header.extend((0..n).map(|i| format!("variance_{}", i)));
+    header.extend((0..m).map(|j| format!("innovation_{}", j)));
+    for i in 0..n {
+        header.extend((0..m).map(|j| format!("gain_{}_{}", i, j)));
+    }
+    writer.write_record(&header)?;
+
+    for record in history {
+        let mut row = vec![record.t.to_string()];
+        row.extend((0..n).map(|i| record.prior[(i, 0)].to_string()));
+        row.extend((0..n).map(|i| record.posterior[(i, 0)].to_string()));
+        row.extend((0..n).map(|i| record.posterior_cov[(i, i)].to_string()));
+        row.extend((0..m).map(|j| match &record.innovation {
+            Some(v) if j < v.row => v[(j, 0)].to_string(),
+            _ => String::new(),
+        }));
+        for i in 0..n {
+            row.extend((0..m).map(|j| match &record.gain {
+                Some(k) if j < k.col => k[(i, j)].to_string(),
+                _ => String::new(),
+            }));
+        }
+        writer.write_record(&row)?;
+    }
+
+    writer.flush()?;
+    return Ok(());
+}
+
+pub fn write_history_to_path<P: AsRef<Path>>(
+    path: P,
+    history: &[StepRecord],
+) -> Result<(), LogError> {
+    return write_history(File::create(path)?, history);
+}
+
+/// `None` for an empty cell
+fn parse_cell(
+    row: &StringRecord,
+    index: usize,
+    row_number: usize,
+    headers: &StringRecord,
+) -> Result<Option<f64>, LogError> {
+    let value = &row[index];
+    if value.is_empty() {
+        return Ok(None);
+    }
+    return value.parse().map(Some).map_err(|_| LogError::InvalidValue {
+        row: row_number,
+        column: headers[index].to_string(),
+        value: value.to_string(),
+    });
+}
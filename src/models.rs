@@ -0,0 +1,202 @@
+use peroxide::prelude::{zeros, Matrix};
+
+/// Discrete transition and process noise for a continuous-time model. States
+/// are grouped per spatial dimension, so a two dimensional constant velocity
+/// model has the state `[x, x_dot, y, y_dot]`.
+pub trait MotionModel {
+    fn state_dim(&self) -> usize;
+    fn phi(&self, dt: f64) -> Matrix;
+    fn q(&self, dt: f64) -> Matrix;
+}
+
+/// Position driven by white noise velocity with spectral density `phis`
+#[derive(Clone, Copy, Debug)]
+pub struct ConstantPosition {
+    pub dims: usize,
+    pub phis: f64,
+}
+
+/// Velocity driven by white noise acceleration with spectral density `phis`
+#[derive(Clone, Copy, Debug)]
+pub struct ConstantVelocity {
+    pub dims: usize,
+    pub phis: f64,
+}
+
+/// Acceleration driven by white noise jerk with spectral density `phis`. This
+/// is the continuous white noise jerk model.
+#[derive(Clone, Copy, Debug)]
+pub struct ConstantAcceleration {
+    pub dims: usize,
+    pub phis: f64,
+}
+
+pub type ContinuousWhiteNoiseJerk = ConstantAcceleration;
+
+/// Acceleration as a first-order Markov process with time constant `tau` and
+/// standard deviation `sigma`, giving a spectral density of `2 sigma^2 / tau`
+#[derive(Clone, Copy, Debug)]
+pub struct Singer {
+    pub dims: usize,
+    pub tau: f64,
+    pub sigma: f64,
+}
+
+impl ConstantPosition {
+    pub fn new(dims: usize, phis: f64) -> ConstantPosition {
+        return ConstantPosition { dims, phis };
+    }
+}
+
+impl ConstantVelocity {
+    pub fn new(dims: usize, phis: f64) -> ConstantVelocity {
+        return ConstantVelocity { dims, phis };
+    }
+}
+
+impl ConstantAcceleration {
+    pub fn new(dims: usize, phis: f64) -> ConstantAcceleration {
+        return ConstantAcceleration { dims, phis };
+    }
+}
+
+impl Singer {
+    pub fn new(dims: usize, tau: f64, sigma: f64) -> Singer {
+        return Singer { dims, tau, sigma };
+    }
+
+    pub fn phis(&self) -> f64 {
+        return 2.0 * self.sigma.powf(2.0) / self.tau;
+    }
+}
+
+impl MotionModel for ConstantPosition {
+    fn state_dim(&self) -> usize {
+        return self.dims;
+    }
+
+    fn phi(&self, dt: f64) -> Matrix {
+        return repeat_block(&polynomial_phi(0, dt), self.dims);
+    }
+
+    fn q(&self, dt: f64) -> Matrix {
+        return repeat_block(&polynomial_q(0, self.phis, dt), self.dims);
+    }
+}
+
+impl MotionModel for ConstantVelocity {
+    fn state_dim(&self) -> usize {
+        return 2 * self.dims;
+    }
+
+    fn phi(&self, dt: f64) -> Matrix {
+        return repeat_block(&polynomial_phi(1, dt), self.dims);
+    }
+
+    fn q(&self, dt: f64) -> Matrix {
+        return repeat_block(&polynomial_q(1, self.phis, dt), self.dims);
+    }
+}
+
+impl MotionModel for ConstantAcceleration {
+    fn state_dim(&self) -> usize {
+        return 3 * self.dims;
+    }
+
+    fn phi(&self, dt: f64) -> Matrix {
+        return repeat_block(&polynomial_phi(2, dt), self.dims);
+    }
+
+    fn q(&self, dt: f64) -> Matrix {
+        return repeat_block(&polynomial_q(2, self.phis, dt), self.dims);
+    }
+}
+
+impl MotionModel for Singer {
+    fn state_dim(&self) -> usize {
+        return 3 * self.dims;
+    }
+
+    fn phi(&self, dt: f64) -> Matrix {
+        let a = 1.0 / self.tau;
+        let e = (-a * dt).exp();
+
+        let mut block = polynomial_phi(2, dt);
+        block[(0, 2)] = (a * dt - 1.0 + e) / a.powf(2.0);
+        block[(1, 2)] = (1.0 - e) / a;
+        block[(2, 2)] = e;
+
+        return repeat_block(&block, self.dims);
+    }
+
+    fn q(&self, dt: f64) -> Matrix {
+        let a = 1.0 / self.tau;
+        let at = a * dt;
+        let e = (-at).exp();
+        let e2 = (-2.0 * at).exp();
+
+        let q11 =
+            (1.0 - e2 + 2.0 * at + 2.0 * at.powf(3.0) / 3.0 - 2.0 * at.powf(2.0) - 4.0 * at * e)
+                / (2.0 * a.powf(5.0));
+        let q12 =
+            (e2 + 1.0 - 2.0 * e + 2.0 * at * e - 2.0 * at + at.powf(2.0)) / (2.0 * a.powf(4.0));
+        let q13 = (1.0 - e2 - 2.0 * at * e) / (2.0 * a.powf(3.0));
+        let q22 = (4.0 * e - 3.0 - e2 + 2.0 * at) / (2.0 * a.powf(3.0));
+        let q23 = (e2 + 1.0 - 2.0 * e) / (2.0 * a.powf(2.0));
+        let q33 = (1.0 - e2) / (2.0 * a);
+
+        let values = [[q11, q12, q13], [q12, q22, q23], [q13, q23, q33]];
+        let mut block = zeros(3, 3);
+        for (i, row) in values.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                block[(i, j)] = self.phis() * value;
+            }
+        }
+
+        return repeat_block(&block, self.dims);
+    }
+}
+
+fn factorial(n: usize) -> f64 {
+    return (1..=n).map(|i| i as f64).product();
+}
+
+/// Transition for a polynomial of the given order in one dimension
+fn polynomial_phi(order: usize, dt: f64) -> Matrix {
+    let n = order + 1;
+    let mut phi = zeros(n, n);
+    for i in 0..n {
+        for j in i..n {
+            phi[(i, j)] = dt.powf((j - i) as f64) / factorial(j - i);
+        }
+    }
+    return phi;
+}
+
+/// Process noise from white noise of spectral density `phis` driving the
+/// highest derivative, integrated exactly over `dt`
+fn polynomial_q(order: usize, phis: f64, dt: f64) -> Matrix {
+    let n = order + 1;
+    let mut q = zeros(n, n);
+    for i in 0..n {
+        for j in 0..n {
+            let power = 2 * order + 1 - i - j;
+            q[(i, j)] = phis * dt.powf(power as f64)
+                / (power as f64 * factorial(order - i) * factorial(order - j));
+        }
+    }
+    return q;
+}
+
+fn repeat_block(block: &Matrix, dims: usize) -> Matrix {
+    let n = block.row;
+    let mut out = zeros(n * dims, n * dims);
+    for d in 0..dims {
+        for i in 0..n {
+            for j in 0..n {
+                out[(d * n + i, d * n + j)] = block[(i, j)];
+            }
+        }
+    }
+    return out;
+}
@@ -0,0 +1,257 @@
+use peroxide::prelude::Matrix;
+
+use crate::{
+    consistency::{chi_square_bounds, nees, nis},
+    filter::StepRecord,
+    rng::{stream_rng, SimulationRng, DEFAULT_SEED},
+};
+
+/// Truth and filter output at one step of one trial
+#[derive(Clone, Debug)]
+pub struct TrialStep {
+    pub t: f64,
+    pub truth: Matrix,
+    pub estimate: Matrix,
+    pub p: Matrix,
+    /// `None` when no measurement was available at this step
+    pub innovation: Option<Matrix>,
+    pub innovation_cov: Option<Matrix>,
+}
+
+impl TrialStep {
+    pub fn from_record(truth: Matrix, record: &StepRecord) -> TrialStep {
+        return TrialStep {
+            t: record.t,
+            truth,
+            estimate: record.posterior.clone(),
+            p: record.posterior_cov.clone(),
+            innovation: record.innovation.clone(),
+            innovation_cov: record.innovation_cov.clone(),
+        };
+    }
+}
+
+/// Ensemble statistics of a Monte Carlo run, indexed by step and then state
+pub struct MonteCarloSummary {
+    pub trials: usize,
+    pub state_dim: usize,
+    pub measurement_dim: usize,
+    pub t: Vec<f64>,
+    /// Root mean square of `truth - estimate` over the trials
+    pub rms_error: Vec<Vec<f64>>,
+    /// Square root of the diagonal of `P` averaged over the trials, which the
+    /// RMS error should match for a well tuned filter
+    pub reported_std: Vec<Vec<f64>>,
+    /// NEES averaged over the trials
+    pub nees: Vec<f64>,
+    /// NIS averaged over the trials with a measurement at the step
+    pub nis: Vec<Option<f64>>,
+    /// Number of trials with a measurement at each step
+    pub nis_samples: Vec<usize>,
+}
+
+/// Runs `trials` independent realisations of a scenario and filter, seeded
+/// with `DEFAULT_SEED`. See `run_seeded`.
+pub fn run<F>(trials: usize, trial: F) -> MonteCarloSummary
+where
+    F: FnMut(usize, &mut SimulationRng) -> Vec<TrialStep>,
+{
+    return run_seeded(trials, DEFAULT_SEED, trial);
+}
+
+/// Runs `trials` independent realisations of a scenario and filter. `trial`
+/// is given the trial index and a generator it should draw all its noise
+/// from, `stream_rng(seed, index)`, so any one trial can be replayed alone.
+/// Every trial must return the same number of steps, with step `k` of every
+/// trial at the same time.
+pub fn run_seeded<F>(trials: usize, seed: u64, mut trial: F) -> MonteCarloSummary
+where
+    F: FnMut(usize, &mut SimulationRng) -> Vec<TrialStep>,
+{
+    assert!(trials > 0, "A Monte Carlo run needs at least one trial");
+
+    let mut t = vec![];
+    let mut state_dim = 0;
+    let mut measurement_dim = 0;
+    let mut squared_error: Vec<Vec<f64>> = vec![];
+    let mut variance: Vec<Vec<f64>> = vec![];
+    let mut nees_sum: Vec<f64> = vec![];
+    let mut nis_sum: Vec<f64> = vec![];
+    let mut nis_samples: Vec<usize> = vec![];
+
+    for index in 0..trials {
+        let steps = trial(index, &mut stream_rng(seed, index as u64));
+
+        if index == 0 {
+            let steps_len = steps.len();
+            state_dim = steps.first().map_or(0, |step| step.truth.row);
+            t = steps.iter().map(|step| step.t).collect();
+            squared_error = vec![vec![0.0; state_dim]; steps_len];
+            variance = vec![vec![0.0; state_dim]; steps_len];
+            nees_sum = vec![0.0; steps_len];
+            nis_sum = vec![0.0; steps_len];
+            nis_samples = vec![0; steps_len];
+        }
+        assert_eq!(
+            steps.len(),
+            t.len(),
+            "Trial {} has a different number of steps",
+            index
+        );
+
+        for (k, step) in steps.iter().enumerate() {
+            let error = &step.truth - &step.estimate;
+            let sums = squared_error[k].iter_mut().zip(variance[k].iter_mut());
+            for (i, (squared, var)) in sums.enumerate() {
+                *squared += error[(i, 0)].powf(2.0);
+                *var += step.p[(i, i)];
+            }
+            nees_sum[k] += nees(&step.truth, &step.estimate, &step.p);
+
+            if let (Some(innovation), Some(s)) = (&step.innovation, &step.innovation_cov) {
+                measurement_dim = innovation.row;
+                nis_sum[k] += nis(innovation, s);
+                nis_samples[k] += 1;
+            }
+        }
+    }
+
+    let n = trials as f64;
+    let mean_sqrt = |sums: Vec<Vec<f64>>| -> Vec<Vec<f64>> {
+        return sums
+            .into_iter()
+            .map(|row| row.into_iter().map(|sum| (sum / n).sqrt()).collect())
+            .collect();
+    };
+
+    return MonteCarloSummary {
+        trials,
+        state_dim,
+        measurement_dim,
+        t,
+        rms_error: mean_sqrt(squared_error),
+        reported_std: mean_sqrt(variance),
+        nees: nees_sum.iter().map(|sum| sum / n).collect(),
+        nis: nis_sum
+            .iter()
+            .zip(nis_samples.iter())
+            .map(|(sum, count)| {
+                if *count == 0 {
+                    None
+                } else {
+                    Some(sum / *count as f64)
+                }
+            })
+            .collect(),
+        nis_samples,
+    };
+}
+
+impl MonteCarloSummary {
+    /// RMS error of one state over time
+    pub fn rms_error_of(&self, state: usize) -> Vec<f64> {
+        return self.rms_error.iter().map(|row| row[state]).collect();
+    }
+
+    /// Reported standard deviation of one state over time
+    pub fn reported_std_of(&self, state: usize) -> Vec<f64> {
+        return self.reported_std.iter().map(|row| row[state]).collect();
+    }
+
+    /// Bounds on the per-step NEES averaged over all trials, `None` for an
+    /// empty state
+    pub fn nees_bounds(&self, confidence: f64) -> Option<(f64, f64)> {
+        if self.state_dim == 0 {
+            return None;
+        }
+        return Some(chi_square_bounds(self.state_dim, self.trials, confidence));
+    }
+
+    /// Bounds on the averaged NIS at `step`, which depend on how many trials
+    /// had a measurement there
+    pub fn nis_bounds(&self, step: usize, confidence: f64) -> Option<(f64, f64)> {
+        if self.nis_samples[step] == 0 {
+            return None;
+        }
+        return Some(chi_square_bounds(
+            self.measurement_dim,
+            self.nis_samples[step],
+            confidence,
+        ));
+    }
+
+    /// NEES averaged over every step and trial, `None` without steps
+    pub fn average_nees(&self) -> Option<f64> {
+        if self.nees.is_empty() {
+            return None;
+        }
+        return Some(self.nees.iter().sum::<f64>() / self.nees.len() as f64);
+    }
+
+    /// NIS averaged over every measurement of every trial, `None` without
+    /// measurements
+    pub fn average_nis(&self) -> Option<f64> {
+        let samples: usize = self.nis_samples.iter().sum();
+        if samples == 0 {
+            return None;
+        }
+        let total: f64 = self
+            .nis
+            .iter()
+            .zip(self.nis_samples.iter())
+            .map(|(value, count)| value.unwrap_or(0.0) * *count as f64)
+            .sum();
+        return Some(total / samples as f64);
+    }
+
+    pub fn average_nees_bounds(&self, confidence: f64) -> Option<(f64, f64)> {
+        let samples = self.trials * self.nees.len();
+        if samples == 0 {
+            return None;
+        }
+        return Some(chi_square_bounds(self.state_dim, samples, confidence));
+    }
+
+    pub fn average_nis_bounds(&self, confidence: f64) -> Option<(f64, f64)> {
+        let samples: usize = self.nis_samples.iter().sum();
+        if samples == 0 {
+            return None;
+        }
+        return Some(chi_square_bounds(self.measurement_dim, samples, confidence));
+    }
+
+    /// Fraction of steps whose averaged NEES lies inside its bounds; about
+    /// `confidence` for a consistent filter. `None` without steps.
+    pub fn nees_fraction_within(&self, confidence: f64) -> Option<f64> {
+        if self.nees.is_empty() {
+            return None;
+        }
+        let (lower, upper) = self.nees_bounds(confidence)?;
+        let within = self
+            .nees
+            .iter()
+            .filter(|value| **value >= lower && **value <= upper)
+            .count();
+        return Some(within as f64 / self.nees.len() as f64);
+    }
+
+    /// Fraction of steps with measurements whose averaged NIS lies inside its
+    /// bounds, `None` if no step has a measurement
+    pub fn nis_fraction_within(&self, confidence: f64) -> Option<f64> {
+        let mut steps = 0;
+        let mut within = 0;
+        for (step, value) in self.nis.iter().enumerate() {
+            if let (Some(value), Some((lower, upper))) = (value, self.nis_bounds(step, confidence))
+            {
+                steps += 1;
+                if *value >= lower && *value <= upper {
+                    within += 1;
+                }
+            }
+        }
+        if steps == 0 {
+            return None;
+        }
+        return Some(within as f64 / steps as f64);
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::VecDeque;
+
+use peroxide::{fuga::LinearAlgebra, prelude::Matrix};
+
+use crate::{
+    filter::{Correction, KalmanFilter},
+    make_s,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OosmMethod {
+    /// One-step retrodiction when the late measurement falls inside the last
+    /// update interval, reprocessing otherwise
+    Retrodiction,
+    /// Always roll back and reprocess the buffer
+    Reprocess,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OosmOutcome {
+    InSequence,
+    Retrodicted,
+    Reprocessed,
+    /// Older than anything left in the buffer
+    Discarded,
+}
+
+#[derive(Clone, Debug)]
+pub struct BufferedMeasurement {
+    pub t: f64,
+    pub z: Matrix,
+    pub h: Matrix,
+    pub r: Matrix,
+    /// Posterior state and covariance after this measurement was applied
+    pub x: Matrix,
+    pub p: Matrix,
+    pub prior_cov: Matrix,
+    pub correction: Correction,
+    /// Whether `x` and `p` can be used as a starting point for reprocessing.
+    /// Retrodicted measurements never are, as their snapshot already contains
+    /// information from later measurements.
+    pub restart: bool,
+}
+
+pub struct OosmFilter {
+    pub filter: KalmanFilter,
+    pub buffer: VecDeque<BufferedMeasurement>,
+    pub capacity: usize,
+    pub method: OosmMethod,
+}
+
+impl OosmFilter {
+    pub fn new(filter: KalmanFilter, capacity: usize, method: OosmMethod) -> OosmFilter {
+        return OosmFilter {
+            filter,
+            buffer: VecDeque::new(),
+            capacity,
+            method,
+        };
+    }
+
+    /// Applies a measurement using the filter's own `h` and `r`
+    pub fn process(&mut self, t: f64, z: &Matrix) -> OosmOutcome {
+        let h = self.filter.h.clone();
+        let r = self.filter.r.clone();
+        return self.process_with(t, z, &h, &r);
+    }
+
+    pub fn process_with(&mut self, t: f64, z: &Matrix, h: &Matrix, r: &Matrix) -> OosmOutcome {
+        if t >= self.filter.t {
+            let entry = self.apply(t, z, h, r);
+            self.push(entry);
+            return OosmOutcome::InSequence;
+        }
+
+        if self.method == OosmMethod::Retrodiction && self.can_retrodict(t) {
+            self.retrodict(t, z, h, r);
+            return OosmOutcome::Retrodicted;
+        }
+
+        return self.reprocess(t, z, h, r);
+    }
+
+    fn apply(&mut self, t: f64, z: &Matrix, h: &Matrix, r: &Matrix) -> BufferedMeasurement {
+        self.filter.predict(t);
+        let prior_cov = self.filter.p.clone();
+        let innovation = z - &(h * &self.filter.x);
+        let correction = self.filter.correct(&innovation, h, r);
+
+        return BufferedMeasurement {
+            t,
+            z: z.clone(),
+            h: h.clone(),
+            r: r.clone(),
+            x: self.filter.x.clone(),
+            p: self.filter.p.clone(),
+            prior_cov,
+            correction,
+            restart: true,
+        };
+    }
+
+    fn push(&mut self, entry: BufferedMeasurement) {
+        self.buffer.push_back(entry);
+        while self.buffer.len() > self.capacity {
+            self.buffer.pop_front();
+        }
+    }
+
+    /// Retrodiction needs the late measurement to sit between the last two
+    /// updates, the filter to still be at the last update, and a linear
+    /// state propagation so the transition can be inverted. Only one late
+    /// measurement per interval can be retrodicted, as the last update's
+    /// stored correction no longer matches the state afterwards; later ones
+    /// are reprocessed.
+    fn can_retrodict(&self, t: f64) -> bool {
+        let n = self.buffer.len();
+        if n < 2 || !self.filter.is_linear() {
+            return false;
+        }
+        let previous = &self.buffer[n - 2];
+        return self.buffer[n - 1].t == self.filter.t && previous.restart && previous.t <= t;
+    }
+
+    /// Bar-Shalom's one-lag (A1) algorithm
+    fn retrodict(&mut self, t: f64, z: &Matrix, h: &Matrix, r: &Matrix) {
+        let last = self
+            .buffer
+            .back()
+            .expect("Retrodiction needs a buffered update");
+        let dt = last.t - t;
+
+        let phi_back = self.filter.phi(dt).inv();
+        let q = self.filter.q(dt);
+        let h_k = &last.h;
+        let s_inv = last.correction.innovation_cov.inv();
+        let x_k = &self.filter.x;
+        let p_k = &self.filter.p;
+
+        let gain_q = &(&(&h_k.t() * &s_inv) * h_k) * &q;
+        let p_vv = &q - &(&q * &gain_q);
+        let p_xv = &q - &(&last.prior_cov * &gain_q);
+
+        let x_correction = &(&(&q * &h_k.t()) * &s_inv) * &last.correction.innovation;
+        let x_tau = &phi_back * &(x_k - &x_correction);
+        let p_tau = &(&phi_back * &(&(&(p_k + &p_vv) - &p_xv) - &p_xv.t())) * &phi_back.t();
+
+        let p_xz = &(&(p_k - &p_xv) * &phi_back.t()) * &h.t();
+        let s_tau = make_s(&p_tau, h, r);
+        let w = &p_xz * &s_tau.inv();
+
+        let innovation = z - &(h * &x_tau);
+        let x_new = x_k + &(&w * &innovation);
+        let p_new = p_k - &(&(&w * &s_tau) * &w.t());
+
+        let entry = BufferedMeasurement {
+            t,
+            z: z.clone(),
+            h: h.clone(),
+            r: r.clone(),
+            x: x_tau,
+            p: p_tau,
+            prior_cov: p_k.clone(),
+            correction: Correction {
+                innovation,
+                innovation_cov: s_tau,
+                gain: w,
+            },
+            restart: false,
+        };
+
+        self.filter.x = x_new.clone();
+        self.filter.p = p_new.clone();
+
+        let n = self.buffer.len();
+        self.buffer[n - 1].x = x_new;
+        self.buffer[n - 1].p = p_new;
+        self.buffer.insert(n - 1, entry);
+        while self.buffer.len() > self.capacity {
+            self.buffer.pop_front();
+        }
+    }
+
+    /// Rolls back to the last restartable snapshot before `t` and replays
+    /// every buffered measurement after it, in time order
+    fn reprocess(&mut self, t: f64, z: &Matrix, h: &Matrix, r: &Matrix) -> OosmOutcome {
+        let insert_at = self
+            .buffer
+            .iter()
+            .position(|e| e.t > t)
+            .unwrap_or(self.buffer.len());
+        let restart = match (0..insert_at).rev().find(|i| self.buffer[*i].restart) {
+            Some(restart) => restart,
+            None => return OosmOutcome::Discarded,
+        };
+
+        let current_t = self.filter.t;
+        let mut replay: Vec<(f64, Matrix, Matrix, Matrix)> = self
+            .buffer
+            .drain(restart + 1..)
+            .map(|e| (e.t, e.z, e.h, e.r))
+            .collect();
+        replay.insert(
+            insert_at - restart - 1,
+            (t, z.clone(), h.clone(), r.clone()),
+        );
+
+        let start = &self.buffer[restart];
+        self.filter.x = start.x.clone();
+        self.filter.p = start.p.clone();
+        self.filter.t = start.t;
+
+        for (t, z, h, r) in replay {
+            let entry = self.apply(t, &z, &h, &r);
+            self.push(entry);
+        }
+        self.filter.predict(current_t);
+
+        return OosmOutcome::Reprocessed;
+    }
+}
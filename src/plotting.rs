@@ -0,0 +1,573 @@
+use std::{
+    f64::consts::PI,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use peroxide::prelude::Matrix;
+use plotly::{
+    common::{DashType, Line, Mode, Title},
+    layout::Axis,
+    Layout, Mesh3D, Plot, Scatter, Scatter3D,
+};
+
+use crate::{
+    filter::StepRecord,
+    linalg::{cholesky_semidefinite, submatrix},
+    stats::chi_square_quantile,
+    svg,
+};
+
+pub const TIME_AXIS: &str = "t (s)";
+// Pinned so saved pages keep rendering the same way
+const PLOTLY_JS: &str = "https://cdn.plot.ly/plotly-2.12.1.min.js";
+const ELLIPSE_POINTS: usize = 72;
+const ELLIPSOID_RINGS: usize = 16;
+
+/// Name and unit of a state or measurement component, used for plot titles and
+/// axis labels
+#[derive(Clone, Debug)]
+pub struct Quantity {
+    pub name: String,
+    pub unit: String,
+}
+
+impl Quantity {
+    pub fn new(name: &str, unit: &str) -> Quantity {
+        return Quantity {
+            name: name.to_string(),
+            unit: unit.to_string(),
+        };
+    }
+
+    /// "Name (unit)"
+    pub fn axis_title(&self) -> String {
+        return format!("{} ({})", self.name, self.unit);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineStyle {
+    Solid,
+    Dash,
+    Dot,
+}
+
+/// One line of a `Figure`. Without a colour the backend picks the next one
+/// from its palette.
+#[derive(Clone, Debug)]
+pub struct Series {
+    pub name: String,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub style: LineStyle,
+    pub color: Option<String>,
+    pub show_legend: bool,
+}
+
+impl Series {
+    pub fn new(name: &str, x: Vec<f64>, y: Vec<f64>) -> Series {
+        return Series {
+            name: name.to_string(),
+            x,
+            y,
+            style: LineStyle::Solid,
+            color: None,
+            show_legend: true,
+        };
+    }
+
+    pub fn with_style(mut self, style: LineStyle) -> Series {
+        self.style = style;
+        return self;
+    }
+
+    pub fn with_color(mut self, color: &str) -> Series {
+        self.color = Some(color.to_string());
+        return self;
+    }
+
+    pub fn without_legend(mut self) -> Series {
+        self.show_legend = false;
+        return self;
+    }
+}
+
+/// A 2D line plot independent of how it is drawn, so the same figure can be
+/// shown with plotly or written to an SVG file on a machine without a browser
+#[derive(Clone, Debug)]
+pub struct Figure {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub series: Vec<Series>,
+    /// Fixed y axis limits, fitted to the data when `None`
+    pub y_range: Option<(f64, f64)>,
+}
+
+impl Figure {
+    pub fn new(title: &str, x_label: &str, y_label: &str) -> Figure {
+        return Figure {
+            title: title.to_string(),
+            x_label: x_label.to_string(),
+            y_label: y_label.to_string(),
+            series: vec![],
+            y_range: None,
+        };
+    }
+
+    pub fn with_y_range(mut self, min: f64, max: f64) -> Figure {
+        assert!(min < max, "The y range must be increasing");
+        self.y_range = Some((min, max));
+        return self;
+    }
+
+    pub fn add(&mut self, series: Series) {
+        self.series.push(series);
+    }
+
+    pub fn to_plotly(&self) -> Plot {
+        let mut plot = Plot::new();
+        for series in &self.series {
+            let mut scatter = Scatter::new(series.x.clone(), series.y.clone()).name(&series.name);
+            if series.style != LineStyle::Solid || series.color.is_some() {
+                let mut line = Line::new();
+                line = match series.style {
+                    LineStyle::Solid => line,
+                    LineStyle::Dash => line.dash(DashType::Dash),
+                    LineStyle::Dot => line.dash(DashType::Dot),
+                };
+                if let Some(color) = &series.color {
+                    line = line.color(color.as_str());
+                }
+                scatter = scatter.mode(Mode::Lines).line(line);
+            }
+            if !series.show_legend {
+                scatter = scatter.show_legend(false);
+            }
+            plot.add_trace(scatter);
+        }
+        let mut y_axis = Axis::default().title(Title::new(&self.y_label));
+        if let Some((min, max)) = self.y_range {
+            y_axis = y_axis.range(vec![min, max]);
+        }
+        plot.set_layout(layout(&self.title, &self.x_label, &self.y_label).y_axis(y_axis));
+        return plot;
+    }
+
+    /// Standalone SVG document, drawn without plotly
+    pub fn to_svg(&self) -> String {
+        return svg::render(self);
+    }
+
+    /// Shows or writes the figure as `output` selects. `name` is the file name
+    /// without extension when writing to a directory.
+    pub fn export(&self, name: &str, output: &PlotOutput) -> io::Result<()> {
+        match output {
+            PlotOutput::Browser => self.to_plotly().show(),
+            PlotOutput::Html(dir) => {
+                let path = output_path(dir, name, "html")?;
+                fs::write(path, html_page(&self.to_plotly()))?;
+            }
+            PlotOutput::Svg(dir) => {
+                let path = output_path(dir, name, "svg")?;
+                fs::write(path, self.to_svg())?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Where figures go. Only `Browser` needs a display; the others write one file
+/// per figure into the given directory, creating it if needed.
+#[derive(Clone, Debug)]
+pub enum PlotOutput {
+    Browser,
+    /// Interactive pages that load plotly.js from its CDN, so viewing them
+    /// needs network access
+    Html(PathBuf),
+    /// Static images that work offline
+    Svg(PathBuf),
+}
+
+impl PlotOutput {
+    /// Reads `PLOT_OUTPUT`, either `browser`, `html` or `svg`, and `PLOT_DIR`
+    /// (default `plots`). When unset, figures open in the browser if one can be
+    /// shown and are written as SVG otherwise, such as on CI or a headless
+    /// Linux machine.
+    pub fn from_env() -> PlotOutput {
+        let dir = PathBuf::from(std::env::var("PLOT_DIR").unwrap_or_else(|_| "plots".to_string()));
+        return match std::env::var("PLOT_OUTPUT").as_deref() {
+            Ok("browser") => PlotOutput::Browser,
+            Ok("html") => PlotOutput::Html(dir),
+            Ok("svg") => PlotOutput::Svg(dir),
+            _ if has_browser() => PlotOutput::Browser,
+            _ => PlotOutput::Svg(dir),
+        };
+    }
+
+    /// Shows or writes a plot built directly with plotly, such as
+    /// `ellipsoid_plot`. The SVG backend only draws 2D figures, so `Svg`
+    /// writes an HTML page instead, which needs network access to view.
+    pub fn export_plotly(&self, plot: &Plot, name: &str) -> io::Result<()> {
+        match self {
+            PlotOutput::Browser => plot.show(),
+            PlotOutput::Html(dir) | PlotOutput::Svg(dir) => {
+                let path = output_path(dir, name, "html")?;
+                fs::write(path, html_page(plot))?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Best guess at whether `show` can open a browser
+fn has_browser() -> bool {
+    if std::env::var_os("CI").is_some() {
+        return false;
+    }
+    if cfg!(any(target_os = "macos", target_os = "windows")) {
+        return true;
+    }
+    return std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some();
+}
+
+fn output_path(dir: &Path, name: &str, extension: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    return Ok(dir.join(format!("{}.{}", name, extension)));
+}
+
+/// Full HTML document for one plot, loading a pinned plotly.js from its CDN
+pub fn html_page(plot: &Plot) -> String {
+    return format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<script src=\"{}\"></script>\n</head>\n<body>\n{}\n</body>\n</html>\n",
+        PLOTLY_JS,
+        plot.to_inline_html(None)
+    );
+}
+
+pub fn layout(title: &str, x_label: &str, y_label: &str) -> Layout {
+    return Layout::default()
+        .title(Title::new(title))
+        .x_axis(Axis::default().title(Title::new(x_label)))
+        .y_axis(Axis::default().title(Title::new(y_label)));
+}
+
+pub fn times(history: &[StepRecord]) -> Vec<f64> {
+    return history.iter().map(|record| record.t).collect();
+}
+
+/// Posterior estimate of one state over a run
+pub fn estimates(history: &[StepRecord], state: usize) -> Vec<f64> {
+    return history
+        .iter()
+        .map(|record| record.posterior[(state, 0)])
+        .collect();
+}
+
+/// Posterior standard deviation of one state over a run
+pub fn std_devs(history: &[StepRecord], state: usize) -> Vec<f64> {
+    return history
+        .iter()
+        .map(|record| record.posterior_cov[(state, state)].sqrt())
+        .collect();
+}
+
+/// One state of a sequence of state vectors, such as a simulated truth
+pub fn component(states: &[Matrix], index: usize) -> Vec<f64> {
+    return states.iter().map(|x| x[(index, 0)]).collect();
+}
+
+/// Estimate of one state over a run, with the truth and the measurements of
+/// that state when they are known
+pub fn estimate_plot(
+    history: &[StepRecord],
+    state: usize,
+    quantity: &Quantity,
+    truth: Option<&[f64]>,
+    measurements: Option<&[f64]>,
+) -> Figure {
+    return estimate_figure(
+        &times(history),
+        &estimates(history, state),
+        quantity,
+        truth,
+        measurements,
+    );
+}
+
+/// `estimate_plot` for a filter that keeps its estimates as plain series
+/// rather than a `StepRecord` history
+pub fn estimate_figure(
+    t: &[f64],
+    estimate: &[f64],
+    quantity: &Quantity,
+    truth: Option<&[f64]>,
+    measurements: Option<&[f64]>,
+) -> Figure {
+    let mut figure = Figure::new(&quantity.name, TIME_AXIS, &quantity.axis_title());
+
+    if let Some(measurements) = measurements {
+        figure.add(Series::new(
+            "Measurements",
+            t.to_vec(),
+            measurements.to_vec(),
+        ));
+    }
+    if let Some(truth) = truth {
+        figure.add(Series::new("Truth", t.to_vec(), truth.to_vec()));
+    }
+    figure.add(Series::new("Filter", t.to_vec(), estimate.to_vec()));
+
+    return figure;
+}
+
+/// Estimate minus truth for one state, inside the filter's own 1 and 3 sigma
+/// envelopes. About 68% and 99.7% of the residual should fall inside them if
+/// the filter is consistent.
+pub fn residual_plot(
+    history: &[StepRecord],
+    state: usize,
+    quantity: &Quantity,
+    truth: &[f64],
+) -> Figure {
+    let residual: Vec<f64> = estimates(history, state)
+        .iter()
+        .zip(truth.iter())
+        .map(|(estimate, truth)| estimate - truth)
+        .collect();
+    return residual_figure(
+        &times(history),
+        &residual,
+        &std_devs(history, state),
+        quantity,
+    );
+}
+
+/// `residual_plot` for a filter that keeps its residuals and standard
+/// deviations as plain series rather than a `StepRecord` history
+pub fn residual_figure(t: &[f64], residual: &[f64], sigma: &[f64], quantity: &Quantity) -> Figure {
+    let mut figure = Figure::new(
+        &format!("{} Residual", quantity.name),
+        TIME_AXIS,
+        &quantity.axis_title(),
+    );
+    figure.add(Series::new("Residual", t.to_vec(), residual.to_vec()));
+    add_envelopes(&mut figure, t, sigma);
+    return figure;
+}
+
+/// Innovations of one measurement component inside the 1 and 3 sigma
+/// envelopes of the innovation covariance. Steps without a measurement are
+/// left out.
+pub fn innovation_plot(history: &[StepRecord], component: usize, quantity: &Quantity) -> Figure {
+    let mut t = vec![];
+    let mut innovation = vec![];
+    let mut sigma = vec![];
+    for record in history {
+        if let (Some(v), Some(s)) = (&record.innovation, &record.innovation_cov) {
+            t.push(record.t);
+            innovation.push(v[(component, 0)]);
+            sigma.push(s[(component, component)].sqrt());
+        }
+    }
+
+    let mut figure = Figure::new(
+        &format!("{} Innovation", quantity.name),
+        TIME_AXIS,
+        &quantity.axis_title(),
+    );
+    figure.add(Series::new("Innovation", t.clone(), innovation));
+    add_envelopes(&mut figure, &t, &sigma);
+    return figure;
+}
+
+/// Estimate and residual plots for each state named in `quantities`, in that
+/// order
+pub fn state_plots(
+    history: &[StepRecord],
+    quantities: &[Quantity],
+    truth: &[Matrix],
+) -> Vec<Figure> {
+    let mut figures = vec![];
+    for (state, quantity) in quantities.iter().enumerate() {
+        let state_truth = component(truth, state);
+        figures.push(estimate_plot(
+            history,
+            state,
+            quantity,
+            Some(state_truth.as_slice()),
+            None,
+        ));
+        figures.push(residual_plot(history, state, quantity, &state_truth));
+    }
+    return figures;
+}
+
+/// Adds `±sigma` dashed and `±3 sigma` dotted bounds
+fn add_envelopes(figure: &mut Figure, t: &[f64], sigma: &[f64]) {
+    let bounds = [(1.0, "±1σ", LineStyle::Dash), (3.0, "±3σ", LineStyle::Dot)];
+    for (scale, name, style) in bounds {
+        let upper: Vec<f64> = sigma.iter().map(|s| scale * s).collect();
+        let lower: Vec<f64> = sigma.iter().map(|s| -scale * s).collect();
+        figure.add(
+            Series::new(name, t.to_vec(), upper)
+                .with_style(style)
+                .with_color("grey"),
+        );
+        figure.add(
+            Series::new(name, t.to_vec(), lower)
+                .with_style(style)
+                .with_color("grey")
+                .without_legend(),
+        );
+    }
+}
+
+/// Boundary of the `confidence` region of states `states` of an estimate `x`
+/// with covariance `p`, closed so the last point repeats the first. The
+/// region holds the true state with probability `confidence` if the filter is
+/// consistent.
+pub fn ellipse(
+    x: &Matrix,
+    p: &Matrix,
+    states: [usize; 2],
+    confidence: f64,
+) -> (Vec<f64>, Vec<f64>) {
+    let l = cholesky_semidefinite(&submatrix(p, &states, &states));
+    let scale = chi_square_quantile(confidence, 2).sqrt();
+
+    let mut xs = vec![];
+    let mut ys = vec![];
+    for i in 0..=ELLIPSE_POINTS {
+        let angle = 2.0 * PI * i as f64 / ELLIPSE_POINTS as f64;
+        let (u, v) = (scale * angle.cos(), scale * angle.sin());
+        xs.push(x[(states[0], 0)] + l[(0, 0)] * u);
+        ys.push(x[(states[1], 0)] + l[(1, 0)] * u + l[(1, 1)] * v);
+    }
+    return (xs, ys);
+}
+
+/// `ellipse` as a grey line, named after its confidence
+pub fn ellipse_series(x: &Matrix, p: &Matrix, states: [usize; 2], confidence: f64) -> Series {
+    let (xs, ys) = ellipse(x, p, states, confidence);
+    return Series::new(&format!("{:.0}% region", 100.0 * confidence), xs, ys).with_color("grey");
+}
+
+/// Estimated track of two states against each other, with the `confidence`
+/// ellipse of their posterior covariance at each of `steps`
+pub fn ellipse_plot(
+    history: &[StepRecord],
+    states: [usize; 2],
+    quantities: [&Quantity; 2],
+    steps: &[usize],
+    confidence: f64,
+) -> Figure {
+    let mut figure = Figure::new(
+        &format!(
+            "{} and {} Uncertainty",
+            quantities[0].name, quantities[1].name
+        ),
+        &quantities[0].axis_title(),
+        &quantities[1].axis_title(),
+    );
+    figure.add(Series::new(
+        "Filter",
+        estimates(history, states[0]),
+        estimates(history, states[1]),
+    ));
+    for (i, step) in steps.iter().enumerate() {
+        let record = &history[*step];
+        let series = ellipse_series(&record.posterior, &record.posterior_cov, states, confidence);
+        figure.add(if i == 0 {
+            series
+        } else {
+            series.without_legend()
+        });
+    }
+    return figure;
+}
+
+/// Surface of the `confidence` region of three states of an estimate `x` with
+/// covariance `p`, as a triangle mesh
+pub fn ellipsoid(
+    x: &Matrix,
+    p: &Matrix,
+    states: [usize; 3],
+    confidence: f64,
+) -> Box<Mesh3D<f64, f64, f64>> {
+    let l = cholesky_semidefinite(&submatrix(p, &states, &states));
+    let scale = chi_square_quantile(confidence, 3).sqrt();
+    let segments = 2 * ELLIPSOID_RINGS;
+
+    // Rings of a unit sphere from pole to pole, mapped through L
+    let mut vertices = [vec![], vec![], vec![]];
+    for ring in 0..=ELLIPSOID_RINGS {
+        let polar = PI * ring as f64 / ELLIPSOID_RINGS as f64;
+        for segment in 0..segments {
+            let azimuth = 2.0 * PI * segment as f64 / segments as f64;
+            let u = [
+                scale * polar.sin() * azimuth.cos(),
+                scale * polar.sin() * azimuth.sin(),
+                scale * polar.cos(),
+            ];
+            for (row, coordinates) in vertices.iter_mut().enumerate() {
+                let offset: f64 = (0..=row).map(|col| l[(row, col)] * u[col]).sum();
+                coordinates.push(x[(states[row], 0)] + offset);
+            }
+        }
+    }
+
+    // Two triangles between each pair of neighbouring rings and segments
+    let (mut i, mut j, mut k) = (vec![], vec![], vec![]);
+    for ring in 0..ELLIPSOID_RINGS {
+        for segment in 0..segments {
+            let next = (segment + 1) % segments;
+            let a = ring * segments + segment;
+            let b = ring * segments + next;
+            let c = (ring + 1) * segments + segment;
+            let d = (ring + 1) * segments + next;
+            i.extend([a, b]);
+            j.extend([b, d]);
+            k.extend([c, c]);
+        }
+    }
+
+    let [xs, ys, zs] = vertices;
+    return Mesh3D::new(xs, ys, zs, Some(i), Some(j), Some(k))
+        .name(&format!("{:.0}% region", 100.0 * confidence))
+        .opacity(0.3);
+}
+
+/// Estimated track of three states in 3D, with the `confidence` ellipsoid of
+/// their posterior covariance at each of `steps`. Being 3D it has no SVG
+/// form; use `PlotOutput::export_plotly` to show or save it.
+pub fn ellipsoid_plot(
+    history: &[StepRecord],
+    states: [usize; 3],
+    steps: &[usize],
+    confidence: f64,
+    title: &str,
+) -> Plot {
+    let mut plot = Plot::new();
+    plot.add_trace(
+        Scatter3D::new(
+            estimates(history, states[0]),
+            estimates(history, states[1]),
+            estimates(history, states[2]),
+        )
+        .name("Filter")
+        .mode(Mode::Lines),
+    );
+    for step in steps {
+        let record = &history[*step];
+        plot.add_trace(ellipsoid(
+            &record.posterior,
+            &record.posterior_cov,
+            states,
+            confidence,
+        ));
+    }
+    plot.set_layout(Layout::default().title(Title::new(title)));
+    return plot;
+}
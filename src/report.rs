@@ -0,0 +1,188 @@
+use std::{fs, io, path::Path};
+
+use peroxide::prelude::Matrix;
+
+use crate::{
+    consistency::{average, chi_square_bounds, nis},
+    filter::StepRecord,
+    plotting::{component, estimates, Figure, Quantity},
+    svg::{escape, render_with_clip_id},
+};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em auto; max-width: 960px; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+td, th { border: 1px solid #ccc; padding: 0.25em 0.6em; text-align: right; }
+th { background: #f3f3f3; text-align: left; }
+figure { margin: 1em 0; }";
+
+/// Single HTML file describing a filter run: its configuration, summary
+/// statistics and figures. Figures are embedded as SVG, so the file needs no
+/// scripts or network access to be viewed.
+pub struct Report {
+    pub title: String,
+    parameters: Vec<(String, String)>,
+    matrices: Vec<(String, Matrix)>,
+    statistics: Vec<(String, String)>,
+    figures: Vec<Figure>,
+}
+
+impl Report {
+    pub fn new(title: &str) -> Report {
+        return Report {
+            title: title.to_string(),
+            parameters: vec![],
+            matrices: vec![],
+            statistics: vec![],
+            figures: vec![],
+        };
+    }
+
+    /// Free-form configuration entry, such as the motion model's name
+    pub fn add_parameter(&mut self, name: &str, value: &str) {
+        self.parameters.push((name.to_string(), value.to_string()));
+    }
+
+    /// Configuration matrix such as `Q`, `R` or the initial `P`, shown as a
+    /// table
+    pub fn add_matrix(&mut self, name: &str, matrix: &Matrix) {
+        self.matrices.push((name.to_string(), matrix.clone()));
+    }
+
+    pub fn add_statistic(&mut self, name: &str, value: f64) {
+        self.statistics
+            .push((name.to_string(), format_value(value)));
+    }
+
+    pub fn add_figure(&mut self, figure: Figure) {
+        self.figures.push(figure);
+    }
+
+    pub fn add_figures(&mut self, figures: Vec<Figure>) {
+        self.figures.extend(figures);
+    }
+
+    /// RMS error of each state named in `quantities` against `truth`
+    pub fn add_rmse(&mut self, history: &[StepRecord], quantities: &[Quantity], truth: &[Matrix]) {
+        for (state, quantity) in quantities.iter().enumerate() {
+            let squared_errors: Vec<f64> = estimates(history, state)
+                .iter()
+                .zip(component(truth, state).iter())
+                .map(|(estimate, truth)| (estimate - truth).powf(2.0))
+                .collect();
+            if let Some(mean_squared_error) = average(&squared_errors) {
+                self.add_statistic(
+                    &format!("RMSE {}", quantity.axis_title()),
+                    mean_squared_error.sqrt(),
+                );
+            }
+        }
+    }
+
+    /// Number of updates, average NIS and its `confidence` region. Steps
+    /// without a measurement are left out.
+    pub fn add_nis(&mut self, history: &[StepRecord], confidence: f64) {
+        let mut values = vec![];
+        let mut dim = 0;
+        for record in history {
+            if let (Some(v), Some(s)) = (&record.innovation, &record.innovation_cov) {
+                values.push(nis(v, s));
+                dim = v.row;
+            }
+        }
+
+        self.add_statistic("Measurement updates", values.len() as f64);
+        let average_nis = match average(&values) {
+            Some(value) => value,
+            None => return,
+        };
+        let (lower, upper) = chi_square_bounds(dim, values.len(), confidence);
+        self.add_statistic("Average NIS", average_nis);
+        self.add_statistic(
+            &format!("Average NIS {:.0}% lower bound", 100.0 * confidence),
+            lower,
+        );
+        self.add_statistic(
+            &format!("Average NIS {:.0}% upper bound", 100.0 * confidence),
+            upper,
+        );
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<h1>{title}</h1>\n",
+            STYLE,
+            title = escape(&self.title)
+        );
+
+        if !self.parameters.is_empty() || !self.matrices.is_empty() {
+            html.push_str("<h2>Configuration</h2>\n");
+            html.push_str(&key_value_table(&self.parameters));
+            for (name, matrix) in &self.matrices {
+                html.push_str(&format!("<h3>{}</h3>\n", escape(name)));
+                html.push_str(&matrix_table(matrix));
+            }
+        }
+
+        if !self.statistics.is_empty() {
+            html.push_str("<h2>Statistics</h2>\n");
+            html.push_str(&key_value_table(&self.statistics));
+        }
+
+        if !self.figures.is_empty() {
+            html.push_str("<h2>Plots</h2>\n");
+            // Inline SVGs share one ID namespace, so each needs its own clip path
+            for (i, figure) in self.figures.iter().enumerate() {
+                let svg = render_with_clip_id(figure, &format!("plot-area-{}", i));
+                html.push_str(&format!("<figure>\n{}</figure>\n", svg));
+            }
+        }
+
+        html.push_str("</body>\n</html>\n");
+        return html;
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        return fs::write(path, self.to_html());
+    }
+}
+
+fn key_value_table(entries: &[(String, String)]) -> String {
+    if entries.is_empty() {
+        return String::new();
+    }
+    let mut table = String::from("<table>\n");
+    for (name, value) in entries {
+        table.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            escape(name),
+            escape(value)
+        ));
+    }
+    table.push_str("</table>\n");
+    return table;
+}
+
+fn matrix_table(matrix: &Matrix) -> String {
+    let mut table = String::from("<table>\n");
+    for i in 0..matrix.row {
+        table.push_str("<tr>");
+        for j in 0..matrix.col {
+            table.push_str(&format!("<td>{}</td>", format_value(matrix[(i, j)])));
+        }
+        table.push_str("</tr>\n");
+    }
+    table.push_str("</table>\n");
+    return table;
+}
+
+/// Whole numbers as they are, otherwise four decimals or scientific notation
+/// for very large or small values
+fn format_value(value: f64) -> String {
+    if value == value.trunc() && value.abs() < 1e9 {
+        return format!("{}", value);
+    }
+    if value.abs() >= 1e5 || value.abs() < 1e-3 {
+        return format!("{:.4e}", value);
+    }
+    return format!("{:.4}", value);
+}
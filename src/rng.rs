@@ -0,0 +1,27 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// Seed used by `default_rng`. Runs that do not pass their own generator or
+/// seed all start from it, so they replay bit-for-bit.
+pub const DEFAULT_SEED: u64 = 42;
+
+/// Portable generator whose output for a given seed does not change between
+/// platforms or releases of `rand`
+pub type SimulationRng = ChaCha8Rng;
+
+pub fn seeded_rng(seed: u64) -> SimulationRng {
+    return ChaCha8Rng::seed_from_u64(seed);
+}
+
+/// Generator seeded with `DEFAULT_SEED`
+pub fn default_rng() -> SimulationRng {
+    return seeded_rng(DEFAULT_SEED);
+}
+
+/// Generator for one of several independent streams from the same seed, such
+/// as the trials of a Monte Carlo run. Any stream can be replayed on its own.
+pub fn stream_rng(seed: u64, stream: u64) -> SimulationRng {
+    let mut rng = seeded_rng(seed);
+    rng.set_stream(stream);
+    return rng;
+}
@@ -0,0 +1,129 @@
+use peroxide::{fuga::LinearAlgebra, prelude::Matrix};
+
+use crate::{linalg::trace, make_k, new_cov, Convergence};
+
+/// Huber threshold giving 95% efficiency for Gaussian noise
+pub const HUBER_THRESHOLD: f64 = 1.345;
+
+pub struct RobustUpdate {
+    pub x_hat: Matrix,
+    pub p: Matrix,
+    /// Effective weight of each measurement component; 1.0 means the component
+    /// was used with its nominal `r`, smaller values mean it was down-weighted
+    pub weights: Vec<f64>,
+    pub iterations: usize,
+}
+
+/// Iteratively reweighted Huber update. `innovation` is `z - h(x_bar)`; for an
+/// extended filter pass the measurement Jacobian as `h`.
+pub fn huber_update(
+    x_bar: &Matrix,
+    m: &Matrix,
+    h: &Matrix,
+    r: &Matrix,
+    innovation: &Matrix,
+    threshold: f64,
+    convergence: &Convergence,
+) -> RobustUpdate {
+    let n = innovation.row;
+    let mut weights = vec![1.0; n];
+    let mut x_hat = x_bar.clone();
+    let mut p = m.clone();
+    let mut iterations = 0;
+
+    while iterations < convergence.max_iterations {
+        iterations += 1;
+
+        let k = make_k(m, h, &reweight(r, &weights));
+        x_hat = x_bar + &(&k * innovation);
+        p = new_cov(&k, h, m);
+
+        let residual = innovation - &(h * &(&x_hat - x_bar));
+        let new_weights: Vec<f64> = (0..n)
+            .map(|i| huber_weight(residual[(i, 0)] / r[(i, i)].sqrt(), threshold))
+            .collect();
+
+        let change = weights
+            .iter()
+            .zip(new_weights.iter())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+
+        // Keep the weights that produced `x_hat` and `p` when stopping
+        if change < convergence.tolerance || iterations == convergence.max_iterations {
+            break;
+        }
+        weights = new_weights;
+    }
+
+    return RobustUpdate {
+        x_hat,
+        p,
+        weights,
+        iterations,
+    };
+}
+
+/// Variational Bayes update assuming Student-t measurement noise with `dof`
+/// degrees of freedom and scale matrix `r`.
+pub fn student_t_update(
+    x_bar: &Matrix,
+    m: &Matrix,
+    h: &Matrix,
+    r: &Matrix,
+    innovation: &Matrix,
+    dof: f64,
+    convergence: &Convergence,
+) -> RobustUpdate {
+    let n = innovation.row;
+    let r_inv = r.inv();
+    let mut lambda = 1.0;
+    let mut x_hat = x_bar.clone();
+    let mut p = m.clone();
+    let mut iterations = 0;
+
+    while iterations < convergence.max_iterations {
+        iterations += 1;
+
+        let k = make_k(m, h, &(r.clone() * (1.0 / lambda)));
+        x_hat = x_bar + &(&k * innovation);
+        p = new_cov(&k, h, m);
+
+        // Expected squared residual under the current posterior
+        let residual = innovation - &(h * &(&x_hat - x_bar));
+        let expected = &(&residual * &residual.t()) + &(h * &p * h.t());
+        let new_lambda = (dof + n as f64) / (dof + trace(&(&r_inv * &expected)));
+
+        let change = (new_lambda - lambda).abs();
+        if change < convergence.tolerance * new_lambda || iterations == convergence.max_iterations {
+            break;
+        }
+        lambda = new_lambda;
+    }
+
+    return RobustUpdate {
+        x_hat,
+        p,
+        weights: vec![lambda; n],
+        iterations,
+    };
+}
+
+fn huber_weight(normalised_residual: f64, threshold: f64) -> f64 {
+    let magnitude = normalised_residual.abs();
+    if magnitude <= threshold {
+        return 1.0;
+    }
+    return threshold / magnitude;
+}
+
+/// `W^-1/2 r W^-1/2`, which keeps any correlation in `r` intact
+fn reweight(r: &Matrix, weights: &[f64]) -> Matrix {
+    let mut r_weighted = r.clone();
+    for (i, w_i) in weights.iter().enumerate() {
+        for (j, w_j) in weights.iter().enumerate() {
+            r_weighted[(i, j)] = r[(i, j)] / (w_i * w_j).sqrt();
+        }
+    }
+    return r_weighted;
+}
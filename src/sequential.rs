@@ -0,0 +1,68 @@
+use peroxide::prelude::{eye, Matrix};
+
+use crate::linalg::{cholesky, is_diagonal, row_matrix, solve_lower};
+
+/// Processes a vector measurement one component at a time, so every
+/// innovation variance is a scalar and no matrix is inverted. A correlated
+/// `r` is first whitened with its Cholesky factor `L`, turning `h`,
+/// `innovation` and `r` into `L^-1 h`, `L^-1 innovation` and `I`.
+///
+/// `innovation` is `z - h(x_bar)`; for an extended filter pass the measurement
+/// Jacobian as `h`. Returns the updated state and covariance.
+pub fn sequential_update(
+    x_bar: &Matrix,
+    m: &Matrix,
+    h: &Matrix,
+    r: &Matrix,
+    innovation: &Matrix,
+) -> (Matrix, Matrix) {
+    let (h, innovation, variances) = if is_diagonal(r) {
+        let variances: Vec<f64> = (0..r.row).map(|i| r[(i, i)]).collect();
+        (h.clone(), innovation.clone(), variances)
+    } else {
+        let l = cholesky(r).expect("Measurement noise must be positive definite");
+        (
+            solve_lower(&l, h),
+            solve_lower(&l, innovation),
+            vec![1.0; r.row],
+        )
+    };
+
+    let mut x = x_bar.clone();
+    let mut p = m.clone();
+
+    for (i, variance) in variances.iter().enumerate() {
+        let h_i = row_matrix(&h, i);
+        let ph = &p * &h_i.t();
+        let s = (&h_i * &ph)[(0, 0)] + variance;
+        let k = ph.clone() * (1.0 / s);
+
+        // Residual of this component against the estimate so far
+        let residual = innovation[(i, 0)] - (&h_i * &(&x - x_bar))[(0, 0)];
+
+        x = &x + &(k.clone() * residual);
+        p = &p - &(&k * &ph.t());
+    }
+
+    return (x, p);
+}
+
+/// Gain of the equivalent batch update, `p h' r^-1` with `p` the covariance
+/// returned by `sequential_update`. Only `r` is inverted, through its
+/// Cholesky factor, so the innovation covariance still never is.
+pub fn sequential_gain(p: &Matrix, h: &Matrix, r: &Matrix) -> Matrix {
+    if is_diagonal(r) {
+        let mut r_inv_h = h.clone();
+        for i in 0..h.row {
+            for j in 0..h.col {
+                r_inv_h[(i, j)] /= r[(i, i)];
+            }
+        }
+        return p * &r_inv_h.t();
+    }
+
+    // h' r^-1 = (L^-1 h)' L^-1
+    let l = cholesky(r).expect("Measurement noise must be positive definite");
+    let whitened = solve_lower(&l, h);
+    return &(p * &whitened.t()) * &solve_lower(&l, &eye(r.row));
+}
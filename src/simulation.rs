@@ -0,0 +1,176 @@
+use peroxide::prelude::Matrix;
+use rand::Rng;
+
+use crate::{
+    gaussian::MultivariateNormal,
+    integrators::Rk45,
+    models::MotionModel,
+    rng::{seeded_rng, DEFAULT_SEED},
+};
+
+/// Ground truth and noisy measurements at a series of times
+#[derive(Clone, Debug, Default)]
+pub struct Dataset {
+    pub t: Vec<f64>,
+    pub truth: Vec<Matrix>,
+    pub measurements: Vec<Matrix>,
+}
+
+impl Dataset {
+    pub fn len(&self) -> usize {
+        return self.t.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.t.is_empty();
+    }
+
+    /// One state of the truth over time
+    pub fn truth_of(&self, state: usize) -> Vec<f64> {
+        return self.truth.iter().map(|x| x[(state, 0)]).collect();
+    }
+
+    /// One component of the measurements over time
+    pub fn measurement_of(&self, component: usize) -> Vec<f64> {
+        return self
+            .measurements
+            .iter()
+            .map(|z| z[(component, 0)])
+            .collect();
+    }
+
+    /// Timestamped measurements in the form taken by `KalmanFilter::run`
+    pub fn timestamped(&self) -> Vec<(f64, Option<Matrix>)> {
+        return self
+            .t
+            .iter()
+            .zip(self.measurements.iter())
+            .map(|(t, z)| (*t, Some(z.clone())))
+            .collect();
+    }
+}
+
+/// Generates ground truth by propagating a dynamics model, with optional
+/// process noise, and measures it through `measurement` with noise of
+/// covariance `r`. Noise comes from a generator seeded with `seed`, which
+/// defaults to `DEFAULT_SEED`, unless one is passed to `run_with_rng`.
+pub struct Simulation {
+    pub r: Matrix,
+    pub seed: u64,
+    dynamics: Box<dyn Fn(f64, &Matrix, f64) -> Matrix>,
+    q: Option<Box<dyn Fn(f64) -> Matrix>>,
+    measurement: Box<dyn Fn(&Matrix) -> Matrix>,
+}
+
+impl Simulation {
+    /// `dynamics(t, x, dt)` gives the noise-free state at `t + dt`
+    pub fn new<D, M>(dynamics: D, measurement: M, r: Matrix) -> Simulation
+    where
+        D: Fn(f64, &Matrix, f64) -> Matrix + 'static,
+        M: Fn(&Matrix) -> Matrix + 'static,
+    {
+        return Simulation {
+            r,
+            seed: DEFAULT_SEED,
+            dynamics: Box::new(dynamics),
+            q: None,
+            measurement: Box::new(measurement),
+        };
+    }
+
+    /// Linear truth from a motion model, measured as `h x`, with process noise
+    /// from the model's `q`
+    pub fn from_model<M>(model: M, h: Matrix, r: Matrix) -> Simulation
+    where
+        M: MotionModel + Clone + 'static,
+    {
+        let q_model = model.clone();
+        return Simulation::new(move |_, x, dt| &model.phi(dt) * x, move |x| &h * x, r)
+            .with_process_noise(move |dt| q_model.q(dt));
+    }
+
+    /// Truth from the continuous dynamics `x_dot = f(t, x)`, integrated with
+    /// the adaptive Runge-Kutta integrator. Running it panics if the
+    /// integrator gives up, as there is then no truth to measure.
+    pub fn from_ode<F, M>(f: F, integrator: Rk45, measurement: M, r: Matrix) -> Simulation
+    where
+        F: Fn(f64, &Matrix) -> Matrix + 'static,
+        M: Fn(&Matrix) -> Matrix + 'static,
+    {
+        let dynamics =
+            move |t: f64, x: &Matrix, dt: f64| match integrator.integrate(&f, t, x, t + dt) {
+                Ok((x, _)) => x,
+                Err(e) => panic!("Could not simulate the truth: {}", e),
+            };
+        return Simulation::new(dynamics, measurement, r);
+    }
+
+    /// Adds zero mean process noise of covariance `q(dt)` after each step
+    pub fn with_process_noise<Q>(mut self, q: Q) -> Simulation
+    where
+        Q: Fn(f64) -> Matrix + 'static,
+    {
+        self.q = Some(Box::new(q));
+        return self;
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Simulation {
+        self.seed = seed;
+        return self;
+    }
+
+    /// Propagates `x0` from `times[0]` through each of `times`, measuring the
+    /// truth at every one. Every call with the same seed gives the same data.
+    pub fn run(&self, x0: &Matrix, times: &[f64]) -> Dataset {
+        return self.run_with_rng(x0, times, &mut seeded_rng(self.seed));
+    }
+
+    /// `run` drawing the noise from `rng`
+    pub fn run_with_rng<R: Rng>(&self, x0: &Matrix, times: &[f64], rng: &mut R) -> Dataset {
+        let mut data = Dataset::default();
+        let mut x = x0.clone();
+        let measurement_noise = MultivariateNormal::zero_mean(self.r.clone());
+
+        for (i, t) in times.iter().enumerate() {
+            if i > 0 {
+                let dt = t - times[i - 1];
+                x = (self.dynamics)(times[i - 1], &x, dt);
+                if let Some(q) = &self.q {
+                    x = &x + &MultivariateNormal::zero_mean(q(dt)).sample(rng);
+                }
+            }
+
+            let z = (self.measurement)(&x);
+            let v = measurement_noise.sample(rng);
+
+            data.t.push(*t);
+            data.measurements.push(&z + &v);
+            data.truth.push(x.clone());
+        }
+
+        return data;
+    }
+
+    /// `run` over evenly spaced times from `t0` while `t < t_end`
+    pub fn run_for(&self, x0: &Matrix, t0: f64, t_end: f64, dt: f64) -> Dataset {
+        return self.run(x0, &even_times(t0, t_end, dt));
+    }
+
+    /// `run_for` drawing the noise from `rng`
+    pub fn run_for_with_rng<R: Rng>(
+        &self,
+        x0: &Matrix,
+        t0: f64,
+        t_end: f64,
+        dt: f64,
+        rng: &mut R,
+    ) -> Dataset {
+        return self.run_with_rng(x0, &even_times(t0, t_end, dt), rng);
+    }
+}
+
+/// Times from `t0` every `dt` while `t < t_end`
+pub(crate) fn even_times(t0: f64, t_end: f64, dt: f64) -> Vec<f64> {
+    let steps = ((t_end - t0) / dt).ceil() as usize;
+    return (0..steps).map(|i| t0 + i as f64 * dt).collect();
+}
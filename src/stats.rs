@@ -0,0 +1,171 @@
+const LANCZOS: [f64; 6] = [
+    76.18009172947146,
+    -86.50532032941677,
+    24.01409824083091,
+    -1.231739572450155,
+    0.1208650973866179e-2,
+    -0.5395239384953e-5,
+];
+
+pub fn ln_gamma(x: f64) -> f64 {
+    let mut y = x;
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    for c in LANCZOS {
+        y += 1.0;
+        series += c / y;
+    }
+
+    return -tmp + (2.5066282746310005 * series / x).ln();
+}
+
+/// Regularised lower incomplete gamma function P(a, x)
+pub fn gamma_p(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    // Both expansions need O(sqrt(a)) terms near x = a, which matters for the
+    // large dof of averaged NEES and NIS
+    let max_iterations = 500 + (10.0 * a.sqrt()) as usize;
+
+    if x < a + 1.0 {
+        // Series representation
+        let mut ap = a;
+        let mut del = 1.0 / a;
+        let mut sum = del;
+        for _ in 0..max_iterations {
+            ap += 1.0;
+            del *= x / ap;
+            sum += del;
+            if del.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        return sum * (-x + a * x.ln() - ln_gamma(a)).exp();
+    }
+
+    // Continued fraction for Q(a, x), evaluated with the modified Lentz method
+    let tiny = 1e-300;
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / tiny;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for i in 1..max_iterations {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = b + an / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1.0 / d;
+        let del = d * c;
+        h *= del;
+        if (del - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    let q = (-x + a * x.ln() - ln_gamma(a)).exp() * h;
+
+    return 1.0 - q;
+}
+
+pub fn chi_square_cdf(x: f64, dof: usize) -> f64 {
+    return gamma_p(dof as f64 / 2.0, x / 2.0);
+}
+
+/// Value below which a chi-square variable with `dof` degrees of freedom falls
+/// with probability `p`
+pub fn chi_square_quantile(p: f64, dof: usize) -> f64 {
+    assert!(p > 0.0 && p < 1.0, "Probability must be in (0, 1)");
+    assert!(dof > 0, "Degrees of freedom must be positive");
+
+    let mut low = 0.0;
+    let mut high = dof as f64;
+    while chi_square_cdf(high, dof) < p {
+        high *= 2.0;
+    }
+
+    for _ in 0..200 {
+        let mid = 0.5 * (low + high);
+        if chi_square_cdf(mid, dof) < p {
+            low = mid;
+        } else {
+            high = mid;
+        }
+        if high - low < 1e-12 * high {
+            break;
+        }
+    }
+
+    return 0.5 * (low + high);
+}
+
+/// Complementary error function, fractional error below 1.2e-7
+pub fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let ans = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+
+    if x >= 0.0 {
+        return ans;
+    }
+    return 2.0 - ans;
+}
+
+pub fn normal_pdf(x: f64) -> f64 {
+    return (-0.5 * x * x).exp() / (2.0 * std::f64::consts::PI).sqrt();
+}
+
+pub fn normal_cdf(x: f64) -> f64 {
+    return 0.5 * erfc(-x / std::f64::consts::SQRT_2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chi_square_quantiles_match_tables() {
+        // (p, dof, tabulated quantile)
+        let table = [
+            (0.95, 1, 3.841459),
+            (0.95, 2, 5.991465),
+            (0.025, 3, 0.2157953),
+            (0.975, 3, 9.348404),
+            (0.95, 10, 18.307038),
+        ];
+        for (p, dof, expected) in table {
+            let quantile = chi_square_quantile(p, dof);
+            assert!(
+                (quantile - expected).abs() < 1e-4 * expected,
+                "chi-square {} quantile with {} dof was {}, expected {}",
+                p,
+                dof,
+                quantile,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn chi_square_quantile_inverts_cdf() {
+        for dof in 1..6 {
+            let quantile = chi_square_quantile(0.9, dof);
+            assert!((chi_square_cdf(quantile, dof) - 0.9).abs() < 1e-9);
+        }
+    }
+}
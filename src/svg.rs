@@ -0,0 +1,286 @@
+use std::fmt::Write;
+
+use crate::plotting::{Figure, LineStyle};
+
+const WIDTH: f64 = 900.0;
+const HEIGHT: f64 = 500.0;
+const MARGIN_LEFT: f64 = 80.0;
+const MARGIN_RIGHT: f64 = 170.0;
+const MARGIN_TOP: f64 = 50.0;
+const MARGIN_BOTTOM: f64 = 60.0;
+const TICKS: f64 = 6.0;
+// Plotly's default trace colours, so both backends look alike
+const PALETTE: [&str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+    "#bcbd22", "#17becf",
+];
+
+/// Draws a figure as a standalone SVG document with axes, ticks, a legend and
+/// one polyline per series. Non-finite points break the line, and points left
+/// on their own are drawn as markers.
+pub fn render(figure: &Figure) -> String {
+    return render_with_clip_id(figure, "plot-area");
+}
+
+/// Like `render`, but names the plot area clip path `clip_id`. Figures that
+/// share one document each need their own ID.
+pub fn render_with_clip_id(figure: &Figure, clip_id: &str) -> String {
+    let (x_min, x_max) = padded_range(figure.series.iter().flat_map(|s| s.x.iter()));
+    let (y_min, y_max) = match figure.y_range {
+        Some(range) => range,
+        None => padded_range(figure.series.iter().flat_map(|s| s.y.iter())),
+    };
+
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let to_x = |x: f64| MARGIN_LEFT + (x - x_min) / (x_max - x_min) * plot_width;
+    let to_y = |y: f64| MARGIN_TOP + (y_max - y) / (y_max - y_min) * plot_height;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"12\">",
+        w = WIDTH,
+        h = HEIGHT
+    )
+    .unwrap();
+    writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>").unwrap();
+
+    // Grid and tick labels
+    for x in ticks(x_min, x_max) {
+        let px = to_x(x);
+        writeln!(
+            svg,
+            "<line x1=\"{px:.1}\" y1=\"{}\" x2=\"{px:.1}\" y2=\"{}\" stroke=\"#e5e5e5\"/>",
+            MARGIN_TOP,
+            MARGIN_TOP + plot_height
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            "<text x=\"{px:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            MARGIN_TOP + plot_height + 18.0,
+            tick_label(x)
+        )
+        .unwrap();
+    }
+    for y in ticks(y_min, y_max) {
+        let py = to_y(y);
+        writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{py:.1}\" x2=\"{}\" y2=\"{py:.1}\" stroke=\"#e5e5e5\"/>",
+            MARGIN_LEFT,
+            MARGIN_LEFT + plot_width
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+            MARGIN_LEFT - 6.0,
+            py + 4.0,
+            tick_label(y)
+        )
+        .unwrap();
+    }
+    writeln!(
+        svg,
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>",
+        MARGIN_LEFT, MARGIN_TOP, plot_width, plot_height
+    )
+    .unwrap();
+
+    // Titles
+    writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"16\">{}</text>",
+        MARGIN_LEFT + plot_width / 2.0,
+        MARGIN_TOP / 2.0 + 5.0,
+        escape(&figure.title)
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+        MARGIN_LEFT + plot_width / 2.0,
+        HEIGHT - 15.0,
+        escape(&figure.x_label)
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        "<text x=\"20\" y=\"{y}\" text-anchor=\"middle\" transform=\"rotate(-90 20 {y})\">{}</text>",
+        escape(&figure.y_label),
+        y = MARGIN_TOP + plot_height / 2.0
+    )
+    .unwrap();
+
+    // Series, clipped to the plot area
+    writeln!(
+        svg,
+        "<clipPath id=\"{}\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></clipPath>",
+        escape(clip_id),
+        MARGIN_LEFT,
+        MARGIN_TOP,
+        plot_width,
+        plot_height
+    )
+    .unwrap();
+    let mut legend_row = 0;
+    let mut next_color = 0;
+    for series in &figure.series {
+        let color = match &series.color {
+            Some(color) => color.clone(),
+            None => {
+                next_color += 1;
+                PALETTE[(next_color - 1) % PALETTE.len()].to_string()
+            }
+        };
+        let dash = match series.style {
+            LineStyle::Solid => "",
+            LineStyle::Dash => " stroke-dasharray=\"6 4\"",
+            LineStyle::Dot => " stroke-dasharray=\"2 3\"",
+        };
+
+        for segment in segments(&series.x, &series.y) {
+            // A lone point has no line to draw, so mark it instead
+            if let [(x, y)] = segment[..] {
+                writeln!(
+                    svg,
+                    "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"3\" fill=\"{}\" clip-path=\"url(#{})\"/>",
+                    to_x(x),
+                    to_y(y),
+                    escape(&color),
+                    escape(clip_id)
+                )
+                .unwrap();
+                continue;
+            }
+            let points: Vec<String> = segment
+                .iter()
+                .map(|(x, y)| format!("{:.2},{:.2}", to_x(*x), to_y(*y)))
+                .collect();
+            writeln!(
+                svg,
+                "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"{} clip-path=\"url(#{})\"/>",
+                points.join(" "),
+                escape(&color),
+                dash,
+                escape(clip_id)
+            )
+            .unwrap();
+        }
+
+        if series.show_legend {
+            let y = MARGIN_TOP + 10.0 + 20.0 * legend_row as f64;
+            let x = MARGIN_LEFT + plot_width + 15.0;
+            writeln!(
+                svg,
+                "<line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"{}\" stroke-width=\"2\"{}/>",
+                x,
+                x + 25.0,
+                escape(&color),
+                dash
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\">{}</text>",
+                x + 32.0,
+                y + 4.0,
+                escape(&series.name)
+            )
+            .unwrap();
+            legend_row += 1;
+        }
+    }
+
+    writeln!(svg, "</svg>").unwrap();
+    return svg;
+}
+
+/// Smallest and largest finite value, widened when they coincide or are too
+/// close together to tell apart at their magnitude
+fn padded_range<'a, I: Iterator<Item = &'a f64>>(values: I) -> (f64, f64) {
+    let mut min = f64::INFINITY;
+    let mut max = f64::NEG_INFINITY;
+    for value in values.filter(|v| v.is_finite()) {
+        min = min.min(*value);
+        max = max.max(*value);
+    }
+    if min > max {
+        return (0.0, 1.0);
+    }
+    let scale = min.abs().max(max.abs());
+    if max - min <= 1e-9 * scale {
+        let pad = if scale == 0.0 { 1.0 } else { 0.5 * scale };
+        return (min - pad, max + pad);
+    }
+    return (min, max);
+}
+
+/// Round tick positions inside `[min, max]`, spaced 1, 2 or 5 times a power of
+/// ten. At most `3 * TICKS` are returned, so a step too small to advance
+/// `tick` cannot loop forever.
+fn ticks(min: f64, max: f64) -> Vec<f64> {
+    let rough = (max - min) / TICKS;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude);
+
+    let mut ticks = vec![];
+    if !step.is_finite() || step <= 0.0 {
+        return ticks;
+    }
+    let first = (min / step).ceil();
+    for i in 0..(3.0 * TICKS) as usize {
+        let tick = (first + i as f64) * step;
+        if tick > max + 1e-9 * step {
+            break;
+        }
+        // Avoid printing -0
+        ticks.push(if tick.abs() < 1e-9 * step { 0.0 } else { tick });
+    }
+    return ticks;
+}
+
+fn tick_label(value: f64) -> String {
+    if value != 0.0 && (value.abs() >= 1e5 || value.abs() < 1e-3) {
+        return format!("{:.1e}", value);
+    }
+    // Enough decimals for the tick spacing, without trailing zeros
+    let label = format!("{:.3}", value);
+    return label
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string();
+}
+
+/// Runs of consecutive finite points
+fn segments(x: &[f64], y: &[f64]) -> Vec<Vec<(f64, f64)>> {
+    let mut segments = vec![];
+    let mut current = vec![];
+    for (x, y) in x.iter().zip(y.iter()) {
+        if x.is_finite() && y.is_finite() {
+            current.push((*x, *y));
+        } else if !current.is_empty() {
+            segments.push(current);
+            current = vec![];
+        }
+    }
+    if !current.is_empty() {
+        segments.push(current);
+    }
+    return segments;
+}
+
+/// Escapes text for use in SVG or HTML content and attributes
+pub fn escape(text: &str) -> String {
+    return text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+}